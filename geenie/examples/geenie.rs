@@ -3,7 +3,7 @@ use core::fmt;
 use geenie::{
     process,
     questions::{confirm, input, select},
-    Cli, Context, File, Geenie, GeenieError, Item, ItemExt,
+    Answers, Cli, Context, File, Geenie, GeenieError, Item, ItemExt,
 };
 use relative_path::RelativePathBuf;
 use spurgt::{core::Env, Asger, Spurgt};
@@ -34,7 +34,10 @@ impl<E: Asger + 'static, C: 'static> Item<E, C> for Test {
             //     content: b"{}".to_vec(),
             // })?;
 
-            env.ask(input("Hello, World")).await?;
+            ctx.ask("hello", env, async |env| {
+                env.ask(input("Hello, World")).await
+            })
+            .await?;
 
             ctx.command(process("pnpm").arg("-h").output(false));
 
@@ -46,13 +49,14 @@ impl<E: Asger + 'static, C: 'static> Item<E, C> for Test {
 fn main() -> Result<(), GeenieError> {
     futures::executor::block_on(async move {
         ctrlc::set_handler(move || {}).expect("setting Ctrl-C handler");
-        let mut m = Geenie::<Cli, ()>::default();
+        let mut m = Geenie::<Cli, ()>::with_answers(Cli::default(), Answers::new());
 
         m.push(Test);
 
         let mut files = m.run(&mut ()).await?;
 
         files.write_to("geenie-test", false).await?;
+        files.session.write_to("geenie-test").await?;
 
         Result::<_, GeenieError>::Ok(())
     })
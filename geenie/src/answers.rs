@@ -0,0 +1,664 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use spurgt::core::{
+    Asger, Choice, Confirm, Env, Error, Input, MultiSelect, Password, Select, SpinnerEvent,
+};
+
+use crate::GeenieError;
+
+#[derive(Debug, Default)]
+pub struct Answers {
+    values: BTreeMap<String, Value>,
+    strict: bool,
+    recorder: Recorder,
+}
+
+/// The answers given during a run. Shared between the answers of a
+/// [`Geenie`](crate::Geenie) and the [`AnswersEnv`] it installs, so questions
+/// asked through either are recorded once.
+#[derive(Debug, Clone, Default)]
+struct Recorder(Arc<Mutex<Recording>>);
+
+#[derive(Debug, Default)]
+struct Recording {
+    session: Session,
+    /// The id of the question [`Context::ask`](crate::Context::ask) is
+    /// currently asking through the env.
+    asking: Option<String>,
+}
+
+impl Recorder {
+    fn lock(&self) -> MutexGuard<'_, Recording> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl Clone for Answers {
+    fn clone(&self) -> Self {
+        Answers {
+            values: self.values.clone(),
+            strict: self.strict,
+            recorder: Recorder(Arc::new(Mutex::new(Recording {
+                session: self.session(),
+                asking: None,
+            }))),
+        }
+    }
+}
+
+impl PartialEq for Answers {
+    fn eq(&self, other: &Self) -> bool {
+        self.values == other.values
+            && self.strict == other.strict
+            && self.session() == other.session()
+    }
+}
+
+impl Answers {
+    pub fn new() -> Answers {
+        Answers::default()
+    }
+
+    pub fn from_json(source: &str) -> Result<Answers, GeenieError> {
+        let values = serde_json::from_str(source).map_err(GeenieError::backend)?;
+        Ok(Answers {
+            values,
+            ..Default::default()
+        })
+    }
+
+    #[cfg(feature = "toml")]
+    pub fn from_toml(source: &str) -> Result<Answers, GeenieError> {
+        let values = toml::from_str(source).map_err(GeenieError::backend)?;
+        Ok(Answers {
+            values,
+            ..Default::default()
+        })
+    }
+
+    #[cfg(feature = "fs")]
+    pub async fn load(path: impl AsRef<std::path::Path>) -> Result<Answers, GeenieError> {
+        let path = path.as_ref();
+        let source = async_fs::read_to_string(path).await?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            #[cfg(feature = "toml")]
+            Some("toml") => Answers::from_toml(&source),
+            _ => Answers::from_json(&source),
+        }
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    pub fn set(
+        &mut self,
+        id: impl ToString,
+        value: impl Serialize,
+    ) -> Result<&mut Self, GeenieError> {
+        let value = serde_json::to_value(value).map_err(GeenieError::backend)?;
+        self.values.insert(id.to_string(), value);
+        Ok(self)
+    }
+
+    pub fn parse_override(&mut self, arg: &str) -> Result<&mut Self, GeenieError> {
+        let Some((id, value)) = arg.split_once('=') else {
+            return Err(GeenieError::backend(format!(
+                "invalid answer override '{arg}', expected key=value"
+            )));
+        };
+
+        self.values
+            .insert(id.trim().to_string(), Value::from(value.to_string()));
+
+        Ok(self)
+    }
+
+    pub fn overrides<I>(&mut self, args: I) -> Result<&mut Self, GeenieError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        for arg in args {
+            self.parse_override(arg.as_ref())?;
+        }
+        Ok(self)
+    }
+
+    pub fn extend(&mut self, answers: Answers) -> &mut Self {
+        self.values.extend(answers.values);
+        self
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.values.contains_key(id)
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, id: &str) -> Result<Option<T>, GeenieError> {
+        let Some(value) = self.values.get(id) else {
+            return Ok(None);
+        };
+
+        coerce(value)
+            .map(Some)
+            .map_err(|err| GeenieError::invalid_answer(id, err))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.values.iter().map(|(id, value)| (id.as_str(), value))
+    }
+
+    pub fn record(&mut self, id: &str, value: impl Serialize) -> Result<(), GeenieError> {
+        self.recorder.lock().session.record(id, value)
+    }
+
+    pub fn session(&self) -> Session {
+        self.recorder.lock().session.clone()
+    }
+
+    pub fn into_session(self) -> Session {
+        self.session()
+    }
+
+    /// A copy of these answers that records into the same session.
+    pub(crate) fn share(&self) -> Answers {
+        Answers {
+            values: self.values.clone(),
+            strict: self.strict,
+            recorder: self.recorder.clone(),
+        }
+    }
+
+    /// Take over the values of `answers`, keep recording where these did.
+    pub(crate) fn replace(&mut self, answers: Answers) {
+        let session = answers.session();
+        self.values = answers.values;
+        self.strict = answers.strict;
+        self.recorder.lock().session = session;
+    }
+
+    pub(crate) fn asking(&mut self, id: Option<&str>) {
+        self.recorder.lock().asking = id.map(ToString::to_string);
+    }
+
+    fn take_asking(&self) -> Option<String> {
+        self.recorder.lock().asking.take()
+    }
+}
+
+fn coerce<T: DeserializeOwned>(value: &Value) -> Result<T, serde_json::Error> {
+    let err = match serde_json::from_value(value.clone()) {
+        Ok(value) => return Ok(value),
+        Err(err) => err,
+    };
+
+    let Value::String(raw) = value else {
+        return Err(err);
+    };
+
+    if let Ok(value) = serde_json::from_str(raw) {
+        return Ok(value);
+    }
+
+    let items = raw
+        .split(',')
+        .map(|item| Value::from(item.trim()))
+        .collect();
+    serde_json::from_value(Value::Array(items)).map_err(|_| err)
+}
+
+/// Answers questions from [`Answers`] before asking the wrapped env, and
+/// records every answer given. Questions asked through
+/// [`Context::ask`](crate::Context::ask) are keyed by their id, others by
+/// their message. Passwords are never recorded.
+pub struct AnswersEnv<E> {
+    env: E,
+    answers: Answers,
+}
+
+impl<E> AnswersEnv<E> {
+    pub fn new(env: E, answers: Answers) -> AnswersEnv<E> {
+        AnswersEnv { env, answers }
+    }
+
+    pub fn answers(&self) -> &Answers {
+        &self.answers
+    }
+
+    pub fn into_inner(self) -> E {
+        self.env
+    }
+
+    fn key(&self, message: &str) -> String {
+        self.answers
+            .take_asking()
+            .unwrap_or_else(|| message.to_string())
+    }
+
+    fn lookup<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, Error> {
+        match self.answers.get(key) {
+            Ok(None) if self.answers.is_strict() => {
+                Err(Error::new(GeenieError::missing_answer(key)))
+            }
+            Ok(answer) => Ok(answer),
+            Err(err) => Err(Error::new(err)),
+        }
+    }
+
+    fn record<T: Serialize>(&mut self, key: &str, answer: T) -> Result<T, Error> {
+        self.answers.record(key, &answer).map_err(Error::new)?;
+        Ok(answer)
+    }
+}
+
+impl<E: Env> Env for AnswersEnv<E> {}
+
+impl<E: Asger> Asger for AnswersEnv<E> {
+    async fn info(&mut self, msg: &str) -> Result<(), Error> {
+        self.env.info(msg).await
+    }
+
+    async fn input(&mut self, question: &Input) -> Result<String, Error> {
+        let key = self.key(&question.message);
+        let answer = match &question.default {
+            Some(default) if self.answers.is_strict() => self
+                .answers
+                .get(&key)
+                .map_err(Error::new)?
+                .or_else(|| Some(default.clone())),
+            _ => self.lookup(&key)?,
+        };
+
+        let answer = match answer {
+            Some(answer) => answer,
+            None => self.env.input(question).await?,
+        };
+
+        self.record(&key, answer)
+    }
+
+    async fn password(&mut self, question: &Password) -> Result<String, Error> {
+        let key = self.key(&question.message);
+        match self.lookup(&key)? {
+            Some(answer) => Ok(answer),
+            None => self.env.password(question).await,
+        }
+    }
+
+    async fn confirm(&mut self, question: &Confirm) -> Result<bool, Error> {
+        let key = self.key(&question.message);
+        let answer = match self.lookup(&key)? {
+            Some(answer) => answer,
+            None => self.env.confirm(question).await?,
+        };
+
+        self.record(&key, answer)
+    }
+
+    async fn select(&mut self, question: &Select) -> Result<usize, Error> {
+        let key = self.key(&question.message);
+        let index = match self.lookup::<Value>(&key)? {
+            Some(answer) => choice(&key, &question.items, &answer)?,
+            None => self.env.select(question).await?,
+        };
+
+        if let Some(item) = question.items.get(index) {
+            self.record(&key, &item.label)?;
+        }
+
+        Ok(index)
+    }
+
+    async fn multi_select(&mut self, question: &MultiSelect) -> Result<Vec<usize>, Error> {
+        let key = self.key(&question.message);
+        let indices = match self.lookup::<Value>(&key)? {
+            Some(answer) => {
+                let answers = match answer {
+                    Value::Array(answers) => answers,
+                    Value::String(answers) => answers
+                        .split(',')
+                        .filter(|answer| !answer.trim().is_empty())
+                        .map(|answer| Value::from(answer.trim()))
+                        .collect(),
+                    answer => vec![answer],
+                };
+
+                answers
+                    .iter()
+                    .map(|answer| choice(&key, &question.items, answer))
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+            None => self.env.multi_select(question).await?,
+        };
+
+        let labels = indices
+            .iter()
+            .filter_map(|index| question.items.get(*index))
+            .map(|item| &item.label)
+            .collect::<Vec<_>>();
+        self.record(&key, labels)?;
+
+        Ok(indices)
+    }
+
+    fn spinner(&mut self, event: SpinnerEvent, message: &str) {
+        self.env.spinner(event, message)
+    }
+}
+
+fn choice(message: &str, items: &[Choice], answer: &Value) -> Result<usize, Error> {
+    let index = match answer {
+        Value::Number(index) => index.as_u64().map(|index| index as usize),
+        Value::String(label) => items
+            .iter()
+            .position(|item| item.label == *label)
+            .or_else(|| label.parse().ok()),
+        _ => None,
+    };
+
+    index.filter(|index| *index < items.len()).ok_or_else(|| {
+        Error::new(GeenieError::invalid_answer(
+            message,
+            format!("{answer} is not one of the options"),
+        ))
+    })
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Session {
+    answers: BTreeMap<String, Value>,
+}
+
+impl Session {
+    pub const FILE: &'static str = ".geenie-answers.json";
+
+    pub fn record(&mut self, id: &str, value: impl Serialize) -> Result<(), GeenieError> {
+        let value = serde_json::to_value(value).map_err(GeenieError::backend)?;
+        self.answers.insert(id.to_string(), value);
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Value> {
+        self.answers.get(id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.answers.iter().map(|(id, value)| (id.as_str(), value))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.answers.is_empty()
+    }
+
+    pub fn to_json(&self) -> Result<String, GeenieError> {
+        serde_json::to_string_pretty(self).map_err(GeenieError::backend)
+    }
+
+    pub fn from_json(source: &str) -> Result<Session, GeenieError> {
+        serde_json::from_str(source).map_err(GeenieError::backend)
+    }
+
+    #[cfg(feature = "fs")]
+    pub async fn write_to(&self, path: impl AsRef<std::path::Path>) -> Result<(), GeenieError> {
+        let path = path.as_ref();
+        async_fs::create_dir_all(path).await?;
+        async_fs::write(path.join(Session::FILE), self.to_json()?).await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "fs")]
+    pub async fn load(path: impl AsRef<std::path::Path>) -> Result<Session, GeenieError> {
+        let source = async_fs::read_to_string(path.as_ref().join(Session::FILE)).await?;
+        Session::from_json(&source)
+    }
+
+    pub fn into_answers(self) -> Answers {
+        Answers {
+            values: self.answers,
+            ..Default::default()
+        }
+    }
+
+    pub fn replay(self) -> Answers {
+        self.into_answers().strict(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use spurgt::{confirm, input, multi_select, select, Spurgt};
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Prompted(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+    impl Prompted {
+        fn push(&self, message: &str) {
+            self.0.lock().unwrap().push(message.to_string());
+        }
+
+        fn asked(&self) -> Vec<String> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl Env for Prompted {}
+
+    impl Asger for Prompted {
+        async fn info(&mut self, _msg: &str) -> Result<(), Error> {
+            Ok(())
+        }
+
+        async fn input(&mut self, question: &Input) -> Result<String, Error> {
+            self.push(&question.message);
+            Ok(String::from("prompted"))
+        }
+
+        async fn password(&mut self, question: &Password) -> Result<String, Error> {
+            self.push(&question.message);
+            Ok(String::from("prompted"))
+        }
+
+        async fn confirm(&mut self, question: &Confirm) -> Result<bool, Error> {
+            self.push(&question.message);
+            Ok(true)
+        }
+
+        async fn select(&mut self, question: &Select) -> Result<usize, Error> {
+            self.push(&question.message);
+            Ok(0)
+        }
+
+        async fn multi_select(&mut self, question: &MultiSelect) -> Result<Vec<usize>, Error> {
+            self.push(&question.message);
+            Ok(Vec::new())
+        }
+    }
+
+    fn overrides(args: &[&str]) -> Answers {
+        let mut answers = Answers::new();
+        answers.overrides(args).unwrap();
+        answers
+    }
+
+    #[test]
+    fn overrides_are_coerced_by_the_asked_type() {
+        let answers = overrides(&["version=1.0", "port=8080", "ci=true", "features=a, b"]);
+
+        assert_eq!(answers.get::<String>("version").unwrap().unwrap(), "1.0");
+        assert_eq!(answers.get::<String>("port").unwrap().unwrap(), "8080");
+        assert_eq!(answers.get::<u16>("port").unwrap(), Some(8080));
+        assert_eq!(answers.get::<bool>("ci").unwrap(), Some(true));
+        assert_eq!(
+            answers.get::<Vec<String>>("features").unwrap().unwrap(),
+            vec!["a", "b"]
+        );
+        assert_eq!(answers.get::<u8>("missing").unwrap(), None);
+        assert!(matches!(
+            answers.get::<bool>("version"),
+            Err(GeenieError::InvalidAnswer { .. })
+        ));
+    }
+
+    #[test]
+    fn override_requires_key_value() {
+        assert!(Answers::new().parse_override("name").is_err());
+        assert_eq!(
+            overrides(&[" name =a=b"]).get::<String>("name").unwrap(),
+            Some(String::from("a=b"))
+        );
+    }
+
+    #[test]
+    fn session_round_trips_and_replays_strictly() {
+        let mut session = Session::default();
+        session.record("name", "demo").unwrap();
+        session.record("port", 8080).unwrap();
+
+        let session = Session::from_json(&session.to_json().unwrap()).unwrap();
+        let answers = session.replay();
+
+        assert!(answers.is_strict());
+        assert_eq!(answers.get::<u16>("port").unwrap(), Some(8080));
+    }
+
+    #[test]
+    fn env_answers_direct_questions() {
+        let mut answers = overrides(&["Name?=demo", "Kind?=bin", "Go?=false"]);
+        answers.set("Features?", ["b", "0"]).unwrap();
+        let prompted = Prompted::default();
+        let mut env = Spurgt::new(AnswersEnv::new(prompted.clone(), answers));
+
+        block_on(async {
+            assert_eq!(env.ask(input("Name?")).await.unwrap(), "demo");
+            assert!(!env.ask(confirm("Go?")).await.unwrap());
+            assert_eq!(
+                env.ask(select("Kind?").item(1, "lib", "").item(2, "bin", ""))
+                    .await
+                    .unwrap(),
+                2
+            );
+            assert_eq!(
+                env.ask(
+                    multi_select("Features?")
+                        .item('a', "a", "")
+                        .item('b', "b", "")
+                )
+                .await
+                .unwrap(),
+                vec!['a', 'b']
+            );
+            assert_eq!(env.ask(input("Other?")).await.unwrap(), "prompted");
+        });
+
+        assert_eq!(prompted.asked(), vec!["Other?"]);
+    }
+
+    #[test]
+    fn strict_env_never_prompts() {
+        let prompted = Prompted::default();
+        let mut env = Spurgt::new(AnswersEnv::new(
+            prompted.clone(),
+            overrides(&["Kind?=other"]).strict(true),
+        ));
+
+        block_on(async {
+            let missing = env.ask(confirm("Overwrite a.txt?")).await.unwrap_err();
+            assert!(matches!(
+                GeenieError::from(missing),
+                GeenieError::MissingAnswer { id } if id == "Overwrite a.txt?"
+            ));
+
+            let invalid = env
+                .ask(select("Kind?").item(1, "lib", ""))
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                GeenieError::from(invalid),
+                GeenieError::InvalidAnswer { .. }
+            ));
+
+            assert_eq!(
+                env.ask(input("Name?").default("demo")).await.unwrap(),
+                "demo"
+            );
+        });
+
+        assert!(prompted.asked().is_empty());
+    }
+
+    struct Asks;
+
+    impl<E: Asger, C> crate::Item<E, C> for Asks {
+        fn process<'a>(
+            self,
+            mut ctx: crate::Context<'a, E, C>,
+            env: &'a mut Spurgt<E>,
+        ) -> impl std::future::Future<Output = Result<(), GeenieError>> + 'a {
+            async move {
+                env.ask(input("Name?")).await?;
+                env.ask(select("Kind?").item(1, "lib", "").item(2, "bin", ""))
+                    .await?;
+                let _: String = ctx
+                    .ask("project", env, async |env| env.ask(input("Project?")).await)
+                    .await?;
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn records_direct_questions_and_replays_them() {
+        let prompted = Prompted::default();
+        let mut geenie = crate::Geenie::<_, ()>::with_answers(prompted.clone(), Answers::new());
+        geenie.push(Asks);
+        let session = block_on(geenie.run(&mut ())).unwrap().session;
+
+        assert_eq!(prompted.asked(), vec!["Name?", "Kind?", "Project?"]);
+        assert_eq!(
+            session.iter().collect::<Vec<_>>(),
+            vec![
+                ("Kind?", &Value::from("lib")),
+                ("Name?", &Value::from("prompted")),
+                ("project", &Value::from("prompted")),
+            ]
+        );
+
+        let replayed = Prompted::default();
+        let mut geenie =
+            crate::Geenie::<_, ()>::with_answers(replayed.clone(), session.clone().replay());
+        geenie.push(Asks);
+        let result = block_on(geenie.run(&mut ())).unwrap();
+
+        assert!(replayed.asked().is_empty());
+        assert_eq!(result.session, session);
+    }
+
+    #[test]
+    fn clones_record_separately_unless_shared() {
+        let mut answers = Answers::new();
+        let shared = answers.share();
+        let mut copy = answers.clone();
+
+        answers.record("a", 1).unwrap();
+        copy.record("b", 2).unwrap();
+
+        assert_eq!(shared.session().get("a"), Some(&Value::from(1)));
+        assert!(shared.session().get("b").is_none());
+        assert!(copy.session().get("a").is_none());
+    }
+}
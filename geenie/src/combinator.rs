@@ -0,0 +1,370 @@
+use core::future::Future;
+
+use spurgt::{confirm, Asger, Spurgt};
+
+use crate::{item::ItemBox, Context, File, GeenieError, Item};
+
+pub struct When<T, F> {
+    item: T,
+    predicate: F,
+    expected: bool,
+}
+
+impl<T, F> When<T, F> {
+    pub fn new(item: T, predicate: F, expected: bool) -> When<T, F> {
+        When {
+            item,
+            predicate,
+            expected,
+        }
+    }
+}
+
+impl<T, F, E, C> Item<E, C> for When<T, F>
+where
+    T: Item<E, C> + 'static,
+    F: FnOnce(&C) -> bool + 'static,
+{
+    fn process<'a>(
+        self,
+        ctx: Context<'a, E, C>,
+        env: &'a mut Spurgt<E>,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            if (self.predicate)(ctx.data()) != self.expected {
+                return Ok(());
+            }
+
+            self.item.process(ctx, env).await
+        }
+    }
+}
+
+pub struct OrElse<T, U> {
+    item: T,
+    fallback: U,
+}
+
+impl<T, U> OrElse<T, U> {
+    pub fn new(item: T, fallback: U) -> OrElse<T, U> {
+        OrElse { item, fallback }
+    }
+}
+
+impl<T, U, E, C> Item<E, C> for OrElse<T, U>
+where
+    T: Item<E, C> + 'static,
+    U: Item<E, C> + 'static,
+    E: 'static,
+    C: Clone + 'static,
+{
+    fn process<'a>(
+        self,
+        mut ctx: Context<'a, E, C>,
+        env: &'a mut Spurgt<E>,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            let answers = ctx.answers.clone();
+            let data = ctx.data().clone();
+
+            let isolated = match ctx.isolate(self.item, env).await {
+                Ok(isolated) => isolated,
+                Err(err) if err.is_tool_failure() => {
+                    ctx.answers.replace(answers);
+                    *ctx.data_mut() = data;
+                    return self.fallback.process(ctx, env).await;
+                }
+                Err(err) => return Err(err),
+            };
+
+            for file in isolated.files {
+                ctx.file(file)?;
+            }
+
+            for cmd in isolated.commands {
+                ctx.files.push_command(cmd);
+            }
+
+            for item in isolated.items {
+                ctx.push(ItemBox(item));
+            }
+
+            Ok(())
+        }
+    }
+}
+
+pub struct Optional<T> {
+    item: T,
+    id: String,
+    prompt: String,
+}
+
+impl<T> Optional<T> {
+    pub fn new(item: T, id: impl ToString, prompt: impl ToString) -> Optional<T> {
+        Optional {
+            item,
+            id: id.to_string(),
+            prompt: prompt.to_string(),
+        }
+    }
+}
+
+impl<T, E, C> Item<E, C> for Optional<T>
+where
+    T: Item<E, C> + 'static,
+    E: Asger,
+{
+    fn process<'a>(
+        self,
+        mut ctx: Context<'a, E, C>,
+        env: &'a mut Spurgt<E>,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            let prompt = self.prompt;
+            let enabled: bool = ctx
+                .ask(&self.id, env, async |env| env.ask(confirm(&prompt)).await)
+                .await?;
+
+            if !enabled {
+                return Ok(());
+            }
+
+            self.item.process(ctx, env).await
+        }
+    }
+}
+
+pub struct MapFiles<T, F> {
+    item: T,
+    map: F,
+}
+
+impl<T, F> MapFiles<T, F> {
+    pub fn new(item: T, map: F) -> MapFiles<T, F> {
+        MapFiles { item, map }
+    }
+}
+
+impl<T, F, E, C> Item<E, C> for MapFiles<T, F>
+where
+    T: Item<E, C> + 'static,
+    F: FnMut(File) -> File + Clone + 'static,
+    E: 'static,
+    C: 'static,
+{
+    fn process<'a>(
+        mut self,
+        mut ctx: Context<'a, E, C>,
+        env: &'a mut Spurgt<E>,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            let isolated = ctx.isolate(self.item, env).await?;
+
+            for file in isolated.files {
+                ctx.file((self.map)(file))?;
+            }
+
+            for cmd in isolated.commands {
+                ctx.files.push_command(cmd);
+            }
+
+            for item in isolated.items {
+                ctx.push(MapFiles {
+                    item: ItemBox(item),
+                    map: self.map.clone(),
+                });
+            }
+
+            Ok(())
+        }
+    }
+}
+
+pub struct FilterFiles<T, F> {
+    item: T,
+    filter: F,
+}
+
+impl<T, F> FilterFiles<T, F> {
+    pub fn new(item: T, filter: F) -> FilterFiles<T, F> {
+        FilterFiles { item, filter }
+    }
+}
+
+impl<T, F, E, C> Item<E, C> for FilterFiles<T, F>
+where
+    T: Item<E, C> + 'static,
+    F: FnMut(&File) -> bool + Clone + 'static,
+    E: 'static,
+    C: 'static,
+{
+    fn process<'a>(
+        mut self,
+        mut ctx: Context<'a, E, C>,
+        env: &'a mut Spurgt<E>,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            let isolated = ctx.isolate(self.item, env).await?;
+
+            for file in isolated.files {
+                if (self.filter)(&file) {
+                    ctx.file(file)?;
+                }
+            }
+
+            for cmd in isolated.commands {
+                ctx.files.push_command(cmd);
+            }
+
+            for item in isolated.items {
+                ctx.push(FilterFiles {
+                    item: ItemBox(item),
+                    filter: self.filter.clone(),
+                });
+            }
+
+            Ok(())
+        }
+    }
+}
+
+pub struct Then<T, U> {
+    item: T,
+    next: U,
+}
+
+impl<T, U> Then<T, U> {
+    pub fn new(item: T, next: U) -> Then<T, U> {
+        Then { item, next }
+    }
+}
+
+impl<T, U, E, C> Item<E, C> for Then<T, U>
+where
+    T: Item<E, C> + 'static,
+    U: Item<E, C> + 'static,
+{
+    fn process<'a>(
+        self,
+        mut ctx: Context<'a, E, C>,
+        env: &'a mut Spurgt<E>,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            self.item.process(ctx.reborrow(), env).await?;
+            ctx.push(self.next);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::{testing::TestEnv, ItemExt, MemoryTarget};
+
+    type Data = Vec<&'static str>;
+
+    fn attempt(
+        error: fn() -> GeenieError,
+    ) -> impl for<'a> FnOnce(
+        Context<'a, TestEnv, Data>,
+        &'a mut Spurgt<TestEnv>,
+    ) -> Result<(), GeenieError>
+           + 'static {
+        move |mut ctx, _env| {
+            ctx.data_mut().push("attempt");
+            ctx.answers.record("tried", true)?;
+            ctx.file(File::new("attempt.txt", "partial"))?;
+            Err(error())
+        }
+    }
+
+    fn render(item: impl Item<TestEnv, Data> + 'static, data: &mut Data) -> MemoryTarget {
+        block_on(TestEnv::new().render(item, data)).unwrap()
+    }
+
+    #[test]
+    fn when_and_unless_follow_the_predicate() {
+        let mut data = vec!["docs"];
+        let target = render(
+            File::new("docs.md", "")
+                .when(|data: &Data| data.contains(&"docs"))
+                .then(File::new("ci.yml", "").unless(|data: &Data| data.contains(&"docs"))),
+            &mut data,
+        );
+
+        assert_eq!(
+            target.files().into_keys().collect::<Vec<_>>(),
+            vec![relative_path::RelativePathBuf::from("docs.md")]
+        );
+    }
+
+    #[test]
+    fn or_else_discards_a_failed_tool_attempt() {
+        let mut data = Data::new();
+        let env = TestEnv::new();
+
+        let mut result = block_on(
+            env.run_item(
+                attempt(|| GeenieError::program_not_found("git", None))
+                    .or_else(File::new("fallback.txt", "ok")),
+                &mut data,
+            ),
+        )
+        .unwrap();
+
+        assert!(data.is_empty());
+        assert!(result.session.get("tried").is_none());
+        let target = MemoryTarget::new();
+        block_on(
+            result
+                .files
+                .write_into(&target, crate::ConflictPolicy::Fail, &mut result.env),
+        )
+        .unwrap();
+        assert_eq!(
+            target.files().into_keys().collect::<Vec<_>>(),
+            vec![relative_path::RelativePathBuf::from("fallback.txt")]
+        );
+    }
+
+    #[test]
+    fn or_else_propagates_other_errors() {
+        for error in [
+            (|| GeenieError::Io(std::io::ErrorKind::Interrupted.into())) as fn() -> GeenieError,
+            || GeenieError::missing_answer("name"),
+        ] {
+            let mut data = Data::new();
+            let err = block_on(
+                TestEnv::new().run_item(attempt(error).or_else(File::new("x", "")), &mut data),
+            )
+            .map(drop)
+            .unwrap_err();
+
+            assert_eq!(err.to_string(), error().to_string());
+        }
+    }
+
+    #[test]
+    fn maps_and_filters_files_of_the_inner_item() {
+        let mut data = Data::new();
+        let target = render(
+            File::new("a.txt", "a")
+                .then(File::new("b.log", "b"))
+                .filter_files(|file| !file.path.as_str().ends_with(".log"))
+                .map_files(|mut file| {
+                    file.path = relative_path::RelativePathBuf::from("out").join(&file.path);
+                    file
+                }),
+            &mut data,
+        );
+
+        assert_eq!(
+            target.files().into_keys().collect::<Vec<_>>(),
+            vec![relative_path::RelativePathBuf::from("out/a.txt")]
+        );
+    }
+}
@@ -1,15 +1,52 @@
-use std::{future::Future, path::Path, pin::Pin};
+use core::fmt;
+use std::{collections::BTreeMap, future::Future, path::Path, pin::Pin};
 
-use spurgt::Spurgt;
+use futures::{
+    future::{self, Either},
+    stream::FuturesUnordered,
+    StreamExt,
+};
+use spurgt::{Asger, Spurgt};
 
 use crate::{GeenieError, Item};
 
+type DetachedFuture<'a> = Pin<Box<dyn Future<Output = Result<(), GeenieError>> + 'a>>;
+
 pub trait Command<E> {
     fn run<'a>(
         &'a self,
         env: &'a mut Spurgt<E>,
         path: &'a Path,
     ) -> impl Future<Output = Result<(), GeenieError>> + 'a;
+
+    fn describe(&self) -> String {
+        core::any::type_name::<Self>().to_string()
+    }
+
+    fn name(&self) -> Option<&str> {
+        None
+    }
+
+    fn dependencies(&self) -> &[String] {
+        &[]
+    }
+
+    fn is_concurrent(&self) -> bool {
+        false
+    }
+
+    /// Why the command should be skipped without failing the run, such as an
+    /// optional tool that is not installed.
+    fn skip_reason(&self) -> Option<String> {
+        None
+    }
+
+    fn run_detached<'a>(
+        &'a self,
+        _path: &'a Path,
+    ) -> Option<impl Future<Output = Result<(), GeenieError>> + 'a> {
+        None::<core::future::Ready<Result<(), GeenieError>>>
+    }
 }
 
 pub trait DynamicCommand<E> {
@@ -18,6 +55,18 @@ pub trait DynamicCommand<E> {
         env: &'a mut Spurgt<E>,
         path: &'a Path,
     ) -> Pin<Box<dyn Future<Output = Result<(), GeenieError>> + 'a>>;
+
+    fn describe(&self) -> String;
+
+    fn name(&self) -> Option<&str>;
+
+    fn dependencies(&self) -> &[String];
+
+    fn is_concurrent(&self) -> bool;
+
+    fn skip_reason(&self) -> Option<String>;
+
+    fn run_detached<'a>(&'a self, path: &'a Path) -> Option<DetachedFuture<'a>>;
 }
 
 pub struct CommandBox<T>(pub T);
@@ -33,21 +82,472 @@ where
     ) -> Pin<Box<dyn Future<Output = Result<(), GeenieError>> + 'a>> {
         Box::pin(async move { self.0.run(env, path).await })
     }
+
+    fn describe(&self) -> String {
+        self.0.describe()
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.0.name()
+    }
+
+    fn dependencies(&self) -> &[String] {
+        self.0.dependencies()
+    }
+
+    fn is_concurrent(&self) -> bool {
+        self.0.is_concurrent()
+    }
+
+    fn skip_reason(&self) -> Option<String> {
+        self.0.skip_reason()
+    }
+
+    fn run_detached<'a>(&'a self, path: &'a Path) -> Option<DetachedFuture<'a>> {
+        let future = self.0.run_detached(path)?;
+        Some(Box::pin(future))
+    }
+}
+
+pub struct Step<T> {
+    command: T,
+    name: Option<String>,
+    after: Vec<String>,
+    concurrent: bool,
+}
+
+impl<T> Step<T> {
+    pub fn new(command: T) -> Step<T> {
+        Step {
+            command,
+            name: None,
+            after: Vec::new(),
+            concurrent: false,
+        }
+    }
+
+    pub fn named(mut self, name: impl ToString) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn after(mut self, name: impl ToString) -> Self {
+        self.after.push(name.to_string());
+        self
+    }
+
+    pub fn concurrent(mut self) -> Self {
+        self.concurrent = true;
+        self
+    }
+}
+
+impl<E, T: Command<E>> Command<E> for Step<T> {
+    fn run<'a>(
+        &'a self,
+        env: &'a mut Spurgt<E>,
+        path: &'a Path,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        self.command.run(env, path)
+    }
+
+    fn describe(&self) -> String {
+        self.command.describe()
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn dependencies(&self) -> &[String] {
+        &self.after
+    }
+
+    fn is_concurrent(&self) -> bool {
+        self.concurrent
+    }
+
+    fn skip_reason(&self) -> Option<String> {
+        self.command.skip_reason()
+    }
+
+    fn run_detached<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> Option<impl Future<Output = Result<(), GeenieError>> + 'a> {
+        self.command.run_detached(path)
+    }
+}
+
+impl<E, C, T> Item<E, C> for Step<T>
+where
+    T: Command<E> + 'static,
+{
+    fn process<'a>(
+        self,
+        mut ctx: crate::Context<'a, E, C>,
+        _env: &'a mut Spurgt<E>,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            ctx.command(self);
+            Ok(())
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CommandStatus {
+    Ran,
+    Failed(GeenieError),
+    Skipped,
+}
+
+impl fmt::Display for CommandStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandStatus::Ran => f.write_str("ran"),
+            CommandStatus::Failed(err) => write!(f, "failed: {err}"),
+            CommandStatus::Skipped => f.write_str("skipped"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CommandRun {
+    pub name: String,
+    pub status: CommandStatus,
+}
+
+#[derive(Debug, Default)]
+pub struct CommandReport {
+    pub commands: Vec<CommandRun>,
+}
+
+impl CommandReport {
+    pub fn ran(&self) -> impl Iterator<Item = &CommandRun> {
+        self.commands
+            .iter()
+            .filter(|run| matches!(run.status, CommandStatus::Ran))
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &CommandRun> {
+        self.commands
+            .iter()
+            .filter(|run| matches!(run.status, CommandStatus::Failed(_)))
+    }
+
+    pub fn skipped(&self) -> impl Iterator<Item = &CommandRun> {
+        self.commands
+            .iter()
+            .filter(|run| matches!(run.status, CommandStatus::Skipped))
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failed().next().is_none()
+    }
+
+    pub fn into_result(mut self) -> Result<CommandReport, GeenieError> {
+        let failed = self
+            .commands
+            .iter()
+            .position(|run| matches!(run.status, CommandStatus::Failed(_)));
+
+        let Some(index) = failed else {
+            return Ok(self);
+        };
+
+        match self.commands.swap_remove(index).status {
+            CommandStatus::Failed(err) => Err(err),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl fmt::Display for CommandReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for run in &self.commands {
+            writeln!(f, "{}: {}", run.name, run.status)?;
+        }
+        Ok(())
+    }
 }
 
 pub struct CommandList<E> {
     cmds: Vec<Box<dyn DynamicCommand<E>>>,
 }
 
+impl<E: Asger> CommandList<E> {
+    pub async fn run_in(
+        &self,
+        env: &mut Spurgt<E>,
+        path: &Path,
+    ) -> Result<CommandReport, GeenieError> {
+        self.run(env, path).await?.into_result()
+    }
+
+    /// Run every command once its dependencies have finished.
+    ///
+    /// Commands that declare no dependencies keep the order of the list: they
+    /// wait for the sequential command before them, and a sequential one also
+    /// waits for the concurrent commands in between. Concurrent commands run
+    /// in the background next to the sequential one holding the environment.
+    /// A command whose dependency failed is skipped. Commands that ask to be
+    /// skipped are reported as such and do not hold up the ones after them.
+    pub async fn run(
+        &self,
+        env: &mut Spurgt<E>,
+        path: &Path,
+    ) -> Result<CommandReport, GeenieError> {
+        let mut scheduler = Scheduler::new(&self.cmds, self.graph()?, path);
+
+        loop {
+            let next = scheduler.start_ready();
+            scheduler.notify(env).await?;
+
+            if let Some(idx) = next {
+                scheduler.started[idx] = true;
+
+                let mut run = self.cmds[idx].run(env, path);
+                let result = loop {
+                    if scheduler.detached.is_empty() {
+                        break run.await;
+                    }
+
+                    match future::select(run, scheduler.detached.next()).await {
+                        Either::Left((result, _)) => break result,
+                        Either::Right((done, pending)) => {
+                            run = pending;
+                            if let Some((done, result)) = done {
+                                scheduler.finish(done, result, true);
+                                scheduler.start_ready();
+                            }
+                        }
+                    }
+                };
+
+                scheduler.finish(idx, result, false);
+                continue;
+            }
+
+            match scheduler.detached.next().await {
+                Some((idx, result)) => scheduler.finish(idx, result, true),
+                None => break,
+            }
+        }
+
+        scheduler.notify(env).await?;
+
+        let commands = self
+            .cmds
+            .iter()
+            .zip(scheduler.status)
+            .map(|(cmd, status)| CommandRun {
+                name: label(cmd.as_ref()),
+                status: status.unwrap_or(CommandStatus::Skipped),
+            })
+            .collect();
+
+        Ok(CommandReport { commands })
+    }
+}
+
 impl<E> CommandList<E> {
-    pub async fn run_in(&self, env: &mut Spurgt<E>, path: &Path) -> Result<(), GeenieError> {
-        for cmd in &self.cmds {
-            cmd.run(env, path).await?;
+    fn graph(&self) -> Result<Vec<Vec<usize>>, GeenieError> {
+        let mut names = BTreeMap::new();
+        for (idx, cmd) in self.cmds.iter().enumerate() {
+            if let Some(name) = cmd.name() {
+                if names.insert(name, idx).is_some() {
+                    return Err(GeenieError::DuplicateCommand {
+                        name: name.to_string(),
+                    });
+                }
+            }
+        }
+
+        let mut previous = None;
+        let mut concurrent = Vec::new();
+        let mut deps = Vec::with_capacity(self.cmds.len());
+        for (idx, cmd) in self.cmds.iter().enumerate() {
+            let declared = cmd
+                .dependencies()
+                .iter()
+                .map(|name| {
+                    names
+                        .get(name.as_str())
+                        .copied()
+                        .ok_or_else(|| GeenieError::UnknownCommand { name: name.clone() })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if !declared.is_empty() {
+                deps.push(declared);
+            } else if cmd.is_concurrent() {
+                deps.push(previous.into_iter().collect());
+                concurrent.push(idx);
+            } else {
+                deps.push(previous.into_iter().chain(concurrent.drain(..)).collect());
+                previous = Some(idx);
+            }
+        }
+
+        let mut done = vec![false; self.cmds.len()];
+        loop {
+            let ready = (0..self.cmds.len())
+                .filter(|&idx| !done[idx] && deps[idx].iter().all(|&dep| done[dep]))
+                .collect::<Vec<_>>();
+            if ready.is_empty() {
+                break;
+            }
+            for idx in ready {
+                done[idx] = true;
+            }
+        }
+
+        let cycle = self
+            .cmds
+            .iter()
+            .zip(&done)
+            .filter(|(_, done)| !**done)
+            .map(|(cmd, _)| label(cmd.as_ref()))
+            .collect::<Vec<_>>();
+
+        if !cycle.is_empty() {
+            return Err(GeenieError::CommandCycle { names: cycle });
+        }
+
+        Ok(deps)
+    }
+
+    pub fn describe(&self) -> Vec<String> {
+        self.cmds.iter().map(|cmd| cmd.describe()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cmds.is_empty()
+    }
+}
+
+type Finished = (usize, Result<(), GeenieError>);
+
+struct Scheduler<'a, E> {
+    cmds: &'a [Box<dyn DynamicCommand<E>>],
+    deps: Vec<Vec<usize>>,
+    path: &'a Path,
+    status: Vec<Option<CommandStatus>>,
+    ok: Vec<bool>,
+    started: Vec<bool>,
+    detached: FuturesUnordered<Pin<Box<dyn Future<Output = Finished> + 'a>>>,
+    notices: Vec<String>,
+}
+
+impl<'a, E> Scheduler<'a, E> {
+    fn new(
+        cmds: &'a [Box<dyn DynamicCommand<E>>],
+        deps: Vec<Vec<usize>>,
+        path: &'a Path,
+    ) -> Scheduler<'a, E> {
+        Scheduler {
+            cmds,
+            deps,
+            path,
+            status: cmds.iter().map(|_| None).collect(),
+            ok: vec![false; cmds.len()],
+            started: vec![false; cmds.len()],
+            detached: FuturesUnordered::new(),
+            notices: Vec::new(),
+        }
+    }
+
+    /// Start every ready concurrent command, skip the ones that ask for it or
+    /// have a failed dependency, and return the first ready command that needs the
+    /// environment.
+    fn start_ready(&mut self) -> Option<usize> {
+        loop {
+            let mut skipped = false;
+            let mut next = None;
+
+            for idx in 0..self.cmds.len() {
+                if self.started[idx]
+                    || !self.deps[idx].iter().all(|&dep| self.status[dep].is_some())
+                {
+                    continue;
+                }
+
+                let cmd = &self.cmds[idx];
+                let blocked = self.deps[idx].iter().any(|&dep| !self.ok[dep]);
+                let reason = (!blocked).then(|| cmd.skip_reason()).flatten();
+                if blocked || reason.is_some() {
+                    if let Some(reason) = reason {
+                        self.notices
+                            .push(format!("Skipping {}: {reason}", label(cmd.as_ref())));
+                    }
+                    self.started[idx] = true;
+                    self.ok[idx] = !blocked;
+                    self.status[idx] = Some(CommandStatus::Skipped);
+                    skipped = true;
+                    continue;
+                }
+
+                match cmd
+                    .is_concurrent()
+                    .then(|| cmd.run_detached(self.path))
+                    .flatten()
+                {
+                    Some(future) => {
+                        self.started[idx] = true;
+                        self.notices
+                            .push(format!("Running {} in the background", label(cmd.as_ref())));
+                        self.detached
+                            .push(Box::pin(async move { (idx, future.await) }));
+                    }
+                    None => {
+                        next.get_or_insert(idx);
+                    }
+                }
+            }
+
+            if !skipped {
+                return next;
+            }
+        }
+    }
+
+    fn finish(&mut self, idx: usize, result: Result<(), GeenieError>, detached: bool) {
+        let name = label(self.cmds[idx].as_ref());
+        self.ok[idx] = result.is_ok();
+        self.status[idx] = Some(match result {
+            Ok(()) => {
+                if detached {
+                    self.notices.push(format!("Finished {name}"));
+                }
+                CommandStatus::Ran
+            }
+            Err(err) => {
+                if detached {
+                    self.notices.push(format!("{name} failed: {err}"));
+                }
+                CommandStatus::Failed(err)
+            }
+        });
+    }
+
+    async fn notify(&mut self, env: &mut Spurgt<E>) -> Result<(), GeenieError>
+    where
+        E: Asger,
+    {
+        for notice in self.notices.drain(..) {
+            env.info(&notice).await.map_err(GeenieError::backend)?;
         }
         Ok(())
     }
 }
 
+fn label<E>(cmd: &dyn DynamicCommand<E>) -> String {
+    cmd.name()
+        .map(ToString::to_string)
+        .unwrap_or_else(|| cmd.describe())
+}
+
 impl<E> From<Vec<Box<dyn DynamicCommand<E>>>> for CommandList<E> {
     fn from(value: Vec<Box<dyn DynamicCommand<E>>>) -> Self {
         CommandList { cmds: value }
@@ -105,3 +605,292 @@ where
         }
     }
 }
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use futures::{channel::oneshot, executor::block_on};
+
+    use super::*;
+    use crate::testing::TestEnv;
+
+    #[derive(Debug, Clone, Default)]
+    struct Log(Arc<Mutex<Vec<&'static str>>>);
+
+    impl Log {
+        fn push(&self, name: &'static str) {
+            self.0.lock().unwrap().push(name);
+        }
+
+        fn get(&self) -> Vec<&'static str> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    struct Task {
+        name: &'static str,
+        after: Vec<String>,
+        concurrent: bool,
+        fail: bool,
+        skip: Option<&'static str>,
+        log: Log,
+        wait: Mutex<Option<oneshot::Receiver<()>>>,
+        release: Mutex<Option<oneshot::Sender<()>>>,
+    }
+
+    impl Task {
+        fn new(name: &'static str, log: &Log) -> Task {
+            Task {
+                name,
+                after: Vec::new(),
+                concurrent: false,
+                fail: false,
+                skip: None,
+                log: log.clone(),
+                wait: Mutex::new(None),
+                release: Mutex::new(None),
+            }
+        }
+
+        fn after(mut self, name: &str) -> Self {
+            self.after.push(name.to_string());
+            self
+        }
+
+        fn concurrent(mut self) -> Self {
+            self.concurrent = true;
+            self
+        }
+
+        fn failing(mut self) -> Self {
+            self.fail = true;
+            self
+        }
+
+        fn skipped(mut self, reason: &'static str) -> Self {
+            self.skip = Some(reason);
+            self
+        }
+
+        async fn work(&self) -> Result<(), GeenieError> {
+            let wait = self.wait.lock().unwrap().take();
+            if let Some(wait) = wait {
+                wait.await.ok();
+            }
+            if let Some(release) = self.release.lock().unwrap().take() {
+                release.send(()).ok();
+            }
+
+            self.log.push(self.name);
+
+            match self.fail {
+                true => Err(GeenieError::backend(format!("{} failed", self.name))),
+                false => Ok(()),
+            }
+        }
+    }
+
+    impl<E> Command<E> for Task {
+        fn run<'a>(
+            &'a self,
+            _env: &'a mut Spurgt<E>,
+            _path: &'a Path,
+        ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+            self.work()
+        }
+
+        fn name(&self) -> Option<&str> {
+            Some(self.name)
+        }
+
+        fn dependencies(&self) -> &[String] {
+            &self.after
+        }
+
+        fn is_concurrent(&self) -> bool {
+            self.concurrent
+        }
+
+        fn skip_reason(&self) -> Option<String> {
+            self.skip.map(ToString::to_string)
+        }
+
+        fn run_detached<'a>(
+            &'a self,
+            _path: &'a Path,
+        ) -> Option<impl Future<Output = Result<(), GeenieError>> + 'a> {
+            self.concurrent.then(|| self.work())
+        }
+    }
+
+    fn run(env: &TestEnv, tasks: Vec<Task>) -> Result<CommandReport, GeenieError> {
+        let list = CommandList::from(
+            tasks
+                .into_iter()
+                .map(|task| Box::new(CommandBox(task)) as Box<dyn DynamicCommand<TestEnv>>)
+                .collect::<Vec<_>>(),
+        );
+        block_on(list.run(&mut Spurgt::new(env.clone()), Path::new(".")))
+    }
+
+    fn status(report: &CommandReport) -> Vec<String> {
+        report
+            .commands
+            .iter()
+            .map(|run| format!("{}: {}", run.name, run.status))
+            .collect()
+    }
+
+    #[test]
+    fn sequential_commands_wait_for_earlier_concurrent_ones() {
+        let log = Log::default();
+        let env = TestEnv::new();
+
+        let report = run(
+            &env,
+            vec![
+                Task::new("install", &log),
+                Task::new("lint", &log).concurrent(),
+                Task::new("format", &log).concurrent(),
+                Task::new("commit", &log),
+            ],
+        )
+        .unwrap();
+
+        assert!(report.is_success());
+        assert_eq!(log.get(), vec!["install", "lint", "format", "commit"]);
+        assert_eq!(
+            env.log(),
+            vec![
+                "Running lint in the background",
+                "Running format in the background",
+                "Finished lint",
+                "Finished format",
+            ]
+        );
+    }
+
+    #[test]
+    fn starts_commands_as_soon_as_their_dependencies_finish() {
+        let log = Log::default();
+        let env = TestEnv::new();
+        let (release, wait) = oneshot::channel();
+
+        let slow = Task::new("slow", &log).after("setup").concurrent();
+        *slow.wait.lock().unwrap() = Some(wait);
+        let unblock = Task::new("unblock", &log).after("fast");
+        *unblock.release.lock().unwrap() = Some(release);
+
+        let report = run(
+            &env,
+            vec![
+                Task::new("setup", &log),
+                slow,
+                Task::new("fast", &log).after("setup").concurrent(),
+                unblock,
+            ],
+        )
+        .unwrap();
+
+        assert!(report.is_success());
+        assert_eq!(log.get(), vec!["setup", "fast", "unblock", "slow"]);
+    }
+
+    #[test]
+    fn declared_dependencies_may_point_forward() {
+        let log = Log::default();
+
+        run(
+            &TestEnv::new(),
+            vec![
+                Task::new("build", &log).after("generate"),
+                Task::new("generate", &log),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(log.get(), vec!["generate", "build"]);
+    }
+
+    #[test]
+    fn skips_commands_after_a_failure() {
+        let log = Log::default();
+
+        let report = run(
+            &TestEnv::new(),
+            vec![
+                Task::new("a", &log).failing(),
+                Task::new("b", &log).concurrent(),
+                Task::new("c", &log).after("a"),
+                Task::new("d", &log),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            status(&report),
+            vec![
+                "a: failed: backend: a failed",
+                "b: skipped",
+                "c: skipped",
+                "d: skipped"
+            ]
+        );
+        assert!(report.into_result().is_err());
+    }
+
+    #[test]
+    fn reports_skipped_commands_without_blocking_later_ones() {
+        let log = Log::default();
+        let env = TestEnv::new();
+
+        let report = run(
+            &env,
+            vec![
+                Task::new("format", &log).skipped("prettier is not installed"),
+                Task::new("lint", &log).after("format"),
+                Task::new("commit", &log),
+            ],
+        )
+        .unwrap();
+
+        assert!(report.is_success());
+        assert_eq!(
+            status(&report),
+            vec!["format: skipped", "lint: ran", "commit: ran"]
+        );
+        assert_eq!(log.get(), vec!["lint", "commit"]);
+        assert_eq!(
+            env.log(),
+            vec!["Skipping format: prettier is not installed"]
+        );
+    }
+
+    #[test]
+    fn rejects_cycles_unknown_and_duplicate_names() {
+        let log = Log::default();
+        let env = TestEnv::new();
+
+        assert!(matches!(
+            run(
+                &env,
+                vec![
+                    Task::new("a", &log).after("b"),
+                    Task::new("b", &log).after("a"),
+                    Task::new("c", &log),
+                ],
+            ),
+            Err(GeenieError::CommandCycle { names }) if names == ["a", "b"]
+        ));
+        assert!(matches!(
+            run(&env, vec![Task::new("a", &log).after("missing")]),
+            Err(GeenieError::UnknownCommand { name }) if name == "missing"
+        ));
+        assert!(matches!(
+            run(&env, vec![Task::new("a", &log), Task::new("a", &log)]),
+            Err(GeenieError::DuplicateCommand { name }) if name == "a"
+        ));
+        assert!(log.get().is_empty());
+    }
+}
@@ -0,0 +1,82 @@
+use core::fmt;
+
+use relative_path::RelativePathBuf;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    Skip,
+    Overwrite,
+    #[default]
+    Fail,
+    Ask,
+    Sidecar,
+    Merge,
+}
+
+impl From<bool> for ConflictPolicy {
+    fn from(force: bool) -> Self {
+        if force {
+            ConflictPolicy::Overwrite
+        } else {
+            ConflictPolicy::Fail
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteOutcome {
+    Created,
+    Overwritten,
+    Unchanged,
+    Skipped,
+    Removed,
+    Sidecar(RelativePathBuf),
+    Merged { conflicts: bool },
+}
+
+impl fmt::Display for WriteOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteOutcome::Created => f.write_str("created"),
+            WriteOutcome::Overwritten => f.write_str("overwritten"),
+            WriteOutcome::Unchanged => f.write_str("unchanged"),
+            WriteOutcome::Skipped => f.write_str("skipped"),
+            WriteOutcome::Removed => f.write_str("removed"),
+            WriteOutcome::Sidecar(path) => write!(f, "written to {path}"),
+            WriteOutcome::Merged { conflicts: false } => f.write_str("merged"),
+            WriteOutcome::Merged { conflicts: true } => f.write_str("merged with conflicts"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrittenFile {
+    pub path: RelativePathBuf,
+    pub outcome: WriteOutcome,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriteReport {
+    pub files: Vec<WrittenFile>,
+}
+
+impl WriteReport {
+    pub fn push(&mut self, path: RelativePathBuf, outcome: WriteOutcome) {
+        self.files.push(WrittenFile { path, outcome });
+    }
+
+    pub fn conflicts(&self) -> impl Iterator<Item = &WrittenFile> {
+        self.files
+            .iter()
+            .filter(|file| matches!(file.outcome, WriteOutcome::Merged { conflicts: true }))
+    }
+}
+
+impl fmt::Display for WriteReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for file in &self.files {
+            writeln!(f, "{}: {}", file.path, file.outcome)?;
+        }
+        Ok(())
+    }
+}
@@ -1,14 +1,56 @@
+use std::path::Path;
+
+use relative_path::RelativePath;
+use serde::{de::DeserializeOwned, Serialize};
+use spurgt::Spurgt;
+
 use crate::{
-    command::{Command, CommandBox},
+    answers::Answers,
+    command::{Command, CommandBox, DynamicCommand},
     item::{DynamicItem, ItemBox},
     result::ResultBuilder,
     File, GeenieError, Item,
 };
 
+pub(crate) struct Isolated<E, C> {
+    pub(crate) files: Vec<File>,
+    pub(crate) commands: Vec<Box<dyn DynamicCommand<E>>>,
+    pub(crate) items: Vec<Box<dyn DynamicItem<E, C>>>,
+}
+
+/// The output of the contexts an isolated item is nested in.
+pub(crate) trait Outer {
+    fn take_file(&mut self, path: &RelativePath) -> Option<File>;
+}
+
+/// The parent side of an isolated context. Files taken from it are kept so
+/// they can be put back when the isolated item fails.
+struct Scope<'a, E> {
+    files: &'a mut ResultBuilder<E>,
+    prefix: &'a RelativePath,
+    outer: Option<&'a mut (dyn Outer + 'a)>,
+    taken: Vec<File>,
+}
+
+impl<E> Outer for Scope<'_, E> {
+    fn take_file(&mut self, path: &RelativePath) -> Option<File> {
+        let path = self.prefix.join(path);
+        if let Some(file) = self.files.take_file(&path) {
+            self.taken.push(file.clone());
+            return Some(file);
+        }
+
+        self.outer.as_mut()?.take_file(&path)
+    }
+}
+
 pub struct Context<'a, E, C> {
     pub(crate) files: &'a mut ResultBuilder<E>,
     pub(crate) questions: &'a mut Vec<Box<dyn DynamicItem<E, C>>>,
     pub(crate) ctx: &'a mut C,
+    pub(crate) answers: &'a mut Answers,
+    pub(crate) root: Option<&'a Path>,
+    pub(crate) outer: Option<&'a mut (dyn Outer + 'a)>,
 }
 
 impl<'a, E, C> Context<'a, E, C> {
@@ -33,6 +75,206 @@ impl<'a, E, C> Context<'a, E, C> {
         self
     }
 
+    pub async fn ask<T, F, R>(
+        &mut self,
+        id: &str,
+        env: &mut Spurgt<E>,
+        question: F,
+    ) -> Result<T, GeenieError>
+    where
+        T: Serialize + DeserializeOwned,
+        F: AsyncFnOnce(&mut Spurgt<E>) -> Result<T, R>,
+        GeenieError: From<R>,
+    {
+        self.answer(id, None, env, question).await
+    }
+
+    /// Like [`Context::ask`], but a strict run without an answer for `id`
+    /// takes `default` instead of failing.
+    pub async fn ask_or<T, F, R>(
+        &mut self,
+        id: &str,
+        default: T,
+        env: &mut Spurgt<E>,
+        question: F,
+    ) -> Result<T, GeenieError>
+    where
+        T: Serialize + DeserializeOwned,
+        F: AsyncFnOnce(&mut Spurgt<E>) -> Result<T, R>,
+        GeenieError: From<R>,
+    {
+        self.answer(id, Some(default), env, question).await
+    }
+
+    async fn answer<T, F, R>(
+        &mut self,
+        id: &str,
+        default: Option<T>,
+        env: &mut Spurgt<E>,
+        question: F,
+    ) -> Result<T, GeenieError>
+    where
+        T: Serialize + DeserializeOwned,
+        F: AsyncFnOnce(&mut Spurgt<E>) -> Result<T, R>,
+        GeenieError: From<R>,
+    {
+        let answer = match (self.answers.get(id)?, default) {
+            (Some(answer), _) => answer,
+            (None, Some(default)) if self.answers.is_strict() => default,
+            (None, None) if self.answers.is_strict() => {
+                return Err(GeenieError::missing_answer(id))
+            }
+            (None, _) => {
+                self.answers.asking(Some(id));
+                let answer = question(env).await;
+                self.answers.asking(None);
+                answer?
+            }
+        };
+
+        self.answers.record(id, &answer)?;
+
+        Ok(answer)
+    }
+
+    pub fn root(&self) -> Option<&Path> {
+        self.root
+    }
+
+    /// Take the current content of a path: a file generated earlier, also by
+    /// the items this one is nested in, or else the file in the target root.
+    pub async fn take_existing(
+        &mut self,
+        path: impl AsRef<RelativePath>,
+    ) -> Result<Option<Vec<u8>>, GeenieError> {
+        Ok(self
+            .take_file(path.as_ref())
+            .await?
+            .map(|file| file.content))
+    }
+
+    /// Take the file an edit applies to, see [`Context::take_existing`]. A
+    /// file generated earlier keeps its settings, one read from the target
+    /// root is overwritten by the edit.
+    pub async fn require_existing(
+        &mut self,
+        path: impl AsRef<RelativePath>,
+    ) -> Result<File, GeenieError> {
+        let path = path.as_ref();
+        self.take_file(path)
+            .await?
+            .ok_or_else(|| GeenieError::missing_file(path.to_relative_path_buf()))
+    }
+
+    async fn take_file(&mut self, path: &RelativePath) -> Result<Option<File>, GeenieError> {
+        if let Some(file) = self.files.take_file(path) {
+            return Ok(Some(file));
+        }
+
+        if let Some(file) = self.outer.as_mut().and_then(|outer| outer.take_file(path)) {
+            return Ok(Some(File {
+                path: path.to_relative_path_buf(),
+                ..file
+            }));
+        }
+
+        #[cfg(feature = "fs")]
+        if let Some(root) = self.root {
+            use crate::target::{FsTarget, Target};
+
+            let content = FsTarget::new(root).read(path).await?;
+            return Ok(content.map(|content| {
+                File::new(path, content).on_conflict(crate::ConflictPolicy::Overwrite)
+            }));
+        }
+
+        Ok(None)
+    }
+
+    pub fn answers(&self) -> &Answers {
+        self.answers
+    }
+
+    pub(crate) fn reborrow(&mut self) -> Context<'_, E, C> {
+        Context {
+            files: self.files,
+            questions: self.questions,
+            ctx: self.ctx,
+            answers: self.answers,
+            root: self.root,
+            outer: match &mut self.outer {
+                Some(outer) => Some(&mut **outer),
+                None => None,
+            },
+        }
+    }
+
+    pub(crate) async fn isolate<T>(
+        &mut self,
+        item: T,
+        env: &mut Spurgt<E>,
+    ) -> Result<Isolated<E, C>, GeenieError>
+    where
+        T: Item<E, C>,
+    {
+        let root = self.root;
+        self.isolate_in(item, env, root, RelativePath::new(""))
+            .await
+    }
+
+    /// Process an item into its own output. Paths the item edits are looked
+    /// up below `prefix` in the output of this context.
+    pub(crate) async fn isolate_in<T>(
+        &mut self,
+        item: T,
+        env: &mut Spurgt<E>,
+        root: Option<&Path>,
+        prefix: &RelativePath,
+    ) -> Result<Isolated<E, C>, GeenieError>
+    where
+        T: Item<E, C>,
+    {
+        let mut files = ResultBuilder::default();
+        let mut items = Vec::default();
+        let mut scope = Scope {
+            files: &mut *self.files,
+            prefix,
+            outer: match &mut self.outer {
+                Some(outer) => Some(&mut **outer),
+                None => None,
+            },
+            taken: Vec::new(),
+        };
+
+        let result = item
+            .process(
+                Context {
+                    files: &mut files,
+                    questions: &mut items,
+                    ctx: &mut *self.ctx,
+                    answers: &mut *self.answers,
+                    root,
+                    outer: Some(&mut scope),
+                },
+                env,
+            )
+            .await;
+
+        if let Err(err) = result {
+            let taken = scope.taken;
+            for file in taken {
+                self.files.push_file(file)?;
+            }
+            return Err(err);
+        }
+
+        Ok(Isolated {
+            files: files.files,
+            commands: files.commands,
+            items,
+        })
+    }
+
     pub fn data_mut(&mut self) -> &mut C {
         self.ctx
     }
@@ -41,3 +283,120 @@ impl<'a, E, C> Context<'a, E, C> {
         self.ctx
     }
 }
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::{item::MountItem, testing::TestEnv, ItemExt};
+
+    struct Edit(&'static str);
+
+    impl<E, C> Item<E, C> for Edit {
+        fn process<'a>(
+            self,
+            mut ctx: Context<'a, E, C>,
+            _env: &'a mut Spurgt<E>,
+        ) -> impl std::future::Future<Output = Result<(), GeenieError>> + 'a {
+            async move {
+                let mut content = ctx.take_existing("a.txt").await?.unwrap_or_default();
+                content.extend_from_slice(self.0.as_bytes());
+                ctx.file(File::new("a.txt", content))?;
+                if self.0 == "fail" {
+                    return Err(GeenieError::program_not_found("git", None));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn nested_items_take_files_from_outer_output() {
+        let target = block_on(TestEnv::new().render(
+            File::new("app/a.txt", "a").then(MountItem::new(
+                "app",
+                MountItem::new("", Edit("b")).map_files(|file| file),
+            )),
+            &mut (),
+        ))
+        .unwrap();
+
+        assert_eq!(target.get("app/a.txt").unwrap(), b"ab");
+    }
+
+    #[test]
+    fn failed_items_give_taken_files_back() {
+        let target = block_on(TestEnv::new().render(
+            File::new("a.txt", "a").then(Edit("fail").or_else(Edit("c"))),
+            &mut (),
+        ))
+        .unwrap();
+
+        assert_eq!(target.get("a.txt").unwrap(), b"ac");
+    }
+
+    #[cfg(all(feature = "json", feature = "toml_edit", feature = "inject"))]
+    #[test]
+    fn edits_overwrite_files_on_disk() {
+        let root = std::env::temp_dir().join(format!("geenie-edits-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("package.json"), "{}\n").unwrap();
+        std::fs::write(root.join("Cargo.toml"), "[package]\n").unwrap();
+        std::fs::write(root.join("src/lib.rs"), "mod a;\n").unwrap();
+
+        let env = TestEnv::new();
+        let mut geenie = env.geenie::<()>();
+        geenie
+            .root(&root)
+            .push(crate::json("package.json").set("/name", "app"))
+            .push(crate::toml("Cargo.toml").set("package.name", "app"))
+            .push(crate::inject("src/lib.rs", "mod b;").mod_list())
+            .push(File::new("new.txt", "new"))
+            .push(crate::json("new.txt").set("/a", 1).map_files(|file| file));
+        let mut result = block_on(env.run(geenie, &mut ())).map(drop);
+        assert!(
+            matches!(&result, Err(GeenieError::Backend(err)) if err.to_string().contains("new.txt")),
+            "generated files keep their content type and settings"
+        );
+
+        let mut geenie = env.geenie::<()>();
+        geenie
+            .root(&root)
+            .push(crate::json("package.json").set("/name", "app"))
+            .push(crate::toml("Cargo.toml").set("package.name", "app"))
+            .push(crate::inject("src/lib.rs", "mod b;").mod_list());
+        let mut output = block_on(env.run(geenie, &mut ())).unwrap();
+        block_on(output.write_to(&root, false)).unwrap();
+
+        let read = |path: &str| std::fs::read_to_string(root.join(path)).unwrap();
+        assert_eq!(read("package.json"), "{\n  \"name\": \"app\"\n}\n");
+        assert_eq!(read("Cargo.toml"), "[package]\nname = \"app\"\n");
+        assert_eq!(read("src/lib.rs"), "mod a;\nmod b;\n");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn edits_keep_the_settings_of_generated_files() {
+        let result = block_on(
+            TestEnv::new().run_item(
+                File::new("a.json", "{}")
+                    .on_conflict(crate::ConflictPolicy::Skip)
+                    .mode(0o600)
+                    .then(crate::json("a.json").set("/a", 1)),
+                &mut (),
+            ),
+        )
+        .map(|result| result.files.into_iter().collect::<Vec<_>>())
+        .unwrap();
+
+        assert_eq!(
+            result,
+            vec![File::new("a.json", "{\n  \"a\": 1\n}")
+                .on_conflict(crate::ConflictPolicy::Skip)
+                .mode(0o600)]
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use core::fmt;
+
+use relative_path::RelativePath;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileDiff {
+    Unchanged,
+    Text(String),
+    Binary { old_len: usize, new_len: usize },
+}
+
+impl FileDiff {
+    pub fn is_unchanged(&self) -> bool {
+        matches!(self, FileDiff::Unchanged)
+    }
+}
+
+impl fmt::Display for FileDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileDiff::Unchanged => f.write_str("files are identical"),
+            FileDiff::Text(diff) => f.write_str(diff),
+            FileDiff::Binary { old_len, new_len } => {
+                write!(f, "binary files differ ({old_len} -> {new_len} bytes)")
+            }
+        }
+    }
+}
+
+pub fn is_binary(content: &[u8]) -> bool {
+    as_text(content).is_none()
+}
+
+fn as_text(content: &[u8]) -> Option<&str> {
+    if content.contains(&0) {
+        return None;
+    }
+    core::str::from_utf8(content).ok()
+}
+
+pub fn diff(path: &RelativePath, old: &[u8], new: &[u8]) -> FileDiff {
+    if old == new {
+        return FileDiff::Unchanged;
+    }
+
+    let (Some(old_text), Some(new_text)) = (as_text(old), as_text(new)) else {
+        return FileDiff::Binary {
+            old_len: old.len(),
+            new_len: new.len(),
+        };
+    };
+
+    let patch = diffy::DiffOptions::new()
+        .set_original_filename(format!("a/{path}"))
+        .set_modified_filename(format!("b/{path}"))
+        .create_patch(old_text, new_text);
+
+    FileDiff::Text(patch.to_string())
+}
+
+pub fn merge(current: &str, generated: &str) -> (String, bool) {
+    let context = current.lines().count() + generated.lines().count() + 1;
+    let patch = diffy::DiffOptions::new()
+        .set_context_len(context)
+        .create_patch(current, generated);
+
+    if patch.hunks().is_empty() {
+        return (current.to_string(), false);
+    }
+
+    let mut merged = Merged::default();
+
+    for hunk in patch.hunks() {
+        for line in hunk.lines() {
+            match line {
+                diffy::Line::Context(line) => {
+                    merged.flush();
+                    merged.output.push_str(line);
+                }
+                diffy::Line::Delete(line) => merged.current.push_str(line),
+                diffy::Line::Insert(line) => merged.generated.push_str(line),
+            }
+        }
+    }
+
+    merged.flush();
+
+    (merged.output, merged.conflicts)
+}
+
+#[derive(Default)]
+struct Merged {
+    output: String,
+    current: String,
+    generated: String,
+    conflicts: bool,
+}
+
+impl Merged {
+    fn flush(&mut self) {
+        let current = core::mem::take(&mut self.current);
+        let generated = core::mem::take(&mut self.generated);
+
+        match (current.is_empty(), generated.is_empty()) {
+            (true, true) => {}
+            (true, false) => self.output.push_str(&generated),
+            (false, true) => self.output.push_str(&current),
+            (false, false) => {
+                self.conflicts = true;
+                self.output.push_str("<<<<<<< current\n");
+                push_line(&mut self.output, &current);
+                self.output.push_str("=======\n");
+                push_line(&mut self.output, &generated);
+                self.output.push_str(">>>>>>> generated\n");
+            }
+        }
+    }
+}
+
+fn push_line(output: &mut String, text: &str) {
+    output.push_str(text);
+    if !text.ends_with('\n') {
+        output.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs_text_with_paths() {
+        let path = RelativePath::new("src/lib.rs");
+
+        assert!(diff(path, b"a\n", b"a\n").is_unchanged());
+        assert_eq!(
+            diff(path, b"a\nb\n", b"a\nc\n").to_string(),
+            "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,2 +1,2 @@\n a\n-b\n+c\n"
+        );
+    }
+
+    #[test]
+    fn reports_binary_sizes() {
+        let path = RelativePath::new("logo.png");
+
+        assert_eq!(
+            diff(path, b"\x89PNG\0", b"GIF"),
+            FileDiff::Binary {
+                old_len: 5,
+                new_len: 3
+            }
+        );
+        assert_eq!(
+            diff(path, &[0xff], b"").to_string(),
+            "binary files differ (1 -> 0 bytes)"
+        );
+        assert!(is_binary(b"a\0b"));
+        assert!(!is_binary("ø".as_bytes()));
+    }
+
+    #[test]
+    fn merges_one_sided_changes_and_marks_conflicts() {
+        assert_eq!(merge("a\nb\n", "a\nb\n"), ("a\nb\n".into(), false));
+        assert_eq!(merge("a\nc\n", "a\nb\nc\n"), ("a\nb\nc\n".into(), false));
+        assert_eq!(merge("a\nlocal\n", "a\n"), ("a\nlocal\n".into(), false));
+        assert_eq!(
+            merge("a\nb\nc", "a\nB\nc"),
+            (
+                "a\n<<<<<<< current\nb\n=======\nB\n>>>>>>> generated\nc".into(),
+                true
+            )
+        );
+    }
+}
@@ -1,3 +1,6 @@
+use core::fmt;
+use std::{path::PathBuf, time::Duration};
+
 use relative_path::RelativePathBuf;
 
 #[derive(Debug, thiserror::Error)]
@@ -8,17 +11,86 @@ pub enum GeenieError {
     Duplicate { path: RelativePathBuf },
     #[error("file already exists: {path}")]
     Exists { path: RelativePathBuf },
+    #[error("cannot edit missing file: {path}")]
+    MissingFile { path: RelativePathBuf },
     #[error("command failed: {error}")]
-    Process { error: String },
+    Command { error: String },
+    #[error("{0}")]
+    Process(Box<ProcessFailure>),
+    #[error(
+        "program not found: {program}, {}",
+        .suggestion.as_ref().map(|name| format!("did you mean `{name}`?")).unwrap_or_else(|| String::from("is it installed and on PATH?"))
+    )]
+    ProgramNotFound {
+        program: String,
+        suggestion: Option<String>,
+    },
+    #[error("duplicate command name: {name}")]
+    DuplicateCommand { name: String },
+    #[error("unknown command dependency: {name}")]
+    UnknownCommand { name: String },
+    #[error("cannot run commands without a target directory: {}", .commands.join(", "))]
+    NoCommandRoot { commands: Vec<String> },
+    #[error("command dependency cycle between {}", .names.join(", "))]
+    CommandCycle { names: Vec<String> },
+    #[error(
+        "missing required tools: {}",
+        .tools.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+    )]
+    MissingTools { tools: Vec<MissingTool> },
+    #[error("anchor not found in {path}: {anchor}")]
+    AnchorNotFound {
+        path: RelativePathBuf,
+        anchor: String,
+    },
+    #[error("{error} (rollback failed: {rollback})")]
+    RollbackFailed {
+        #[source]
+        error: Box<GeenieError>,
+        rollback: Box<GeenieError>,
+    },
     #[error("backend: {0}")]
     Backend(Box<dyn std::error::Error + Send + Sync>),
     #[error("{0}")]
-    Spurgt(#[from] spurgt::core::Error),
+    Spurgt(spurgt::core::Error),
+    #[error("missing answer for question: {id}")]
+    MissingAnswer { id: String },
+    #[error("invalid answer for question {id}: {message}")]
+    InvalidAnswer { id: String, message: String },
+    #[error(
+        "template error in {path}{}: {message}",
+        .line.map(|line| format!(" at line {line}")).unwrap_or_default()
+    )]
+    Template {
+        path: String,
+        line: Option<usize>,
+        message: String,
+    },
 }
 
 impl GeenieError {
     pub fn is_io(&self) -> bool {
-        matches!(self, Self::Io(_))
+        matches!(self.original(), Self::Io(_))
+    }
+
+    /// Whether an external program failed, could not be found or was not
+    /// recent enough.
+    pub fn is_tool_failure(&self) -> bool {
+        matches!(
+            self.original(),
+            Self::Command { .. }
+                | Self::Process(_)
+                | Self::ProgramNotFound { .. }
+                | Self::MissingTools { .. }
+        )
+    }
+
+    /// The error that caused a failed rollback, or this error itself.
+    pub fn original(&self) -> &GeenieError {
+        match self {
+            Self::RollbackFailed { error, .. } => error.original(),
+            error => error,
+        }
     }
 
     pub fn duplicate(path: RelativePathBuf) -> GeenieError {
@@ -29,11 +101,203 @@ impl GeenieError {
         GeenieError::Exists { path }
     }
 
+    pub fn missing_file(path: RelativePathBuf) -> GeenieError {
+        GeenieError::MissingFile { path }
+    }
+
+    pub fn missing_answer(id: impl ToString) -> GeenieError {
+        GeenieError::MissingAnswer { id: id.to_string() }
+    }
+
+    pub fn invalid_answer(id: impl ToString, message: impl ToString) -> GeenieError {
+        GeenieError::InvalidAnswer {
+            id: id.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    #[deprecated(note = "use `GeenieError::process` with a `ProcessFailure`")]
     pub fn command(error: String) -> GeenieError {
-        GeenieError::Process { error }
+        GeenieError::Command { error }
+    }
+
+    pub fn process(failure: ProcessFailure) -> GeenieError {
+        GeenieError::Process(Box::new(failure))
+    }
+
+    pub fn program_not_found(program: impl ToString, suggestion: Option<String>) -> GeenieError {
+        GeenieError::ProgramNotFound {
+            program: program.to_string(),
+            suggestion,
+        }
+    }
+
+    pub fn anchor_not_found(path: RelativePathBuf, anchor: impl ToString) -> GeenieError {
+        GeenieError::AnchorNotFound {
+            path,
+            anchor: anchor.to_string(),
+        }
+    }
+
+    #[cfg(feature = "template")]
+    pub fn template(path: impl ToString, error: minijinja::Error) -> GeenieError {
+        let message = match error.detail() {
+            Some(detail) => format!("{}: {}", error.kind(), detail),
+            None => error.kind().to_string(),
+        };
+
+        GeenieError::Template {
+            path: error
+                .name()
+                .map(ToString::to_string)
+                .unwrap_or(path.to_string()),
+            line: error.line(),
+            message,
+        }
+    }
+
+    pub fn rollback_failed(error: GeenieError, rollback: GeenieError) -> GeenieError {
+        GeenieError::RollbackFailed {
+            error: Box::new(error),
+            rollback: Box::new(rollback),
+        }
     }
 
     pub fn backend<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> GeenieError {
         GeenieError::Backend(error.into())
     }
 }
+
+impl From<spurgt::core::Error> for GeenieError {
+    fn from(error: spurgt::core::Error) -> Self {
+        match error.into_inner().downcast::<GeenieError>() {
+            Ok(error) => *error,
+            Err(error) => GeenieError::Spurgt(spurgt::core::Error::new(error)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitReason {
+    Code(i32),
+    Signal(i32),
+    TimedOut(Duration),
+}
+
+impl fmt::Display for ExitReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExitReason::Code(code) => write!(f, "exited with code {code}"),
+            ExitReason::Signal(signal) => write!(f, "was killed by signal {signal}"),
+            ExitReason::TimedOut(timeout) => write!(f, "timed out after {timeout:?}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessFailure {
+    pub program: String,
+    pub args: Vec<String>,
+    pub cwd: PathBuf,
+    pub reason: ExitReason,
+    pub stdout: Vec<String>,
+    pub stderr: Vec<String>,
+    pub duration: Duration,
+}
+
+impl ProcessFailure {
+    pub fn command_line(&self) -> String {
+        core::iter::once(self.program.as_str())
+            .chain(self.args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl fmt::Display for ProcessFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` in {} {} after {:.1?}",
+            self.command_line(),
+            self.cwd.display(),
+            self.reason,
+            self.duration
+        )?;
+
+        let tail = if self.stderr.is_empty() {
+            &self.stdout
+        } else {
+            &self.stderr
+        };
+        for line in tail {
+            write!(f, "\n  {line}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolProblem {
+    NotFound { suggestion: Option<String> },
+    Outdated { found: String, required: String },
+    UnknownVersion { required: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingTool {
+    pub name: String,
+    pub problem: ToolProblem,
+}
+
+impl fmt::Display for MissingTool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.problem {
+            ToolProblem::NotFound {
+                suggestion: Some(suggestion),
+            } => write!(f, "{} (not found, did you mean `{suggestion}`?)", self.name),
+            ToolProblem::NotFound { suggestion: None } => write!(f, "{} (not found)", self.name),
+            ToolProblem::Outdated { found, required } => {
+                write!(f, "{} (found {found}, need >= {required})", self.name)
+            }
+            ToolProblem::UnknownVersion { required } => {
+                write!(
+                    f,
+                    "{} (could not determine version, need >= {required})",
+                    self.name
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use super::*;
+
+    #[test]
+    fn rollback_failures_keep_the_original_error() {
+        let err = GeenieError::rollback_failed(
+            GeenieError::program_not_found("npm", None),
+            GeenieError::backend("disk full"),
+        );
+
+        assert!(err.is_tool_failure());
+        assert!(!err.is_io());
+        assert!(matches!(
+            err.original(),
+            GeenieError::ProgramNotFound { program, .. } if program == "npm"
+        ));
+        assert_eq!(
+            err.source().map(ToString::to_string).as_deref(),
+            Some("program not found: npm, is it installed and on PATH?")
+        );
+        assert_eq!(
+            err.to_string(),
+            "program not found: npm, is it installed and on PATH? (rollback failed: backend: disk full)"
+        );
+    }
+}
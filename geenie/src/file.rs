@@ -1,14 +1,35 @@
-#[cfg(feature = "fs")]
+use std::borrow::Cow;
+
 use futures::StreamExt;
 use relative_path::RelativePathBuf;
-use spurgt::Spurgt;
+use spurgt::{confirm, Asger, Spurgt};
 
-use crate::{GeenieError, Item};
+#[cfg(feature = "fs")]
+use crate::target::FsTarget;
+use crate::{
+    conflict::{WriteOutcome, WriteReport},
+    diff::FileDiff,
+    plan::{FileAction, PlannedFile},
+    target::Target,
+};
+use crate::{ConflictPolicy, GeenieError, Item, MergeStrategy};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum FileKind {
+    #[default]
+    File,
+    Symlink(String),
+    Dir,
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct File {
     pub path: RelativePathBuf,
     pub content: Vec<u8>,
+    pub conflict: Option<ConflictPolicy>,
+    pub merge: MergeStrategy,
+    pub mode: Option<u32>,
+    pub kind: FileKind,
 }
 
 impl File {
@@ -16,21 +37,268 @@ impl File {
         File {
             path: path.into(),
             content: content.into(),
+            conflict: None,
+            merge: MergeStrategy::Error,
+            mode: None,
+            kind: FileKind::File,
+        }
+    }
+
+    pub fn symlink(path: impl Into<RelativePathBuf>, target: impl ToString) -> File {
+        File {
+            kind: FileKind::Symlink(target.to_string()),
+            ..File::new(path, Vec::new())
         }
     }
 
+    pub fn dir(path: impl Into<RelativePathBuf>) -> File {
+        File {
+            kind: FileKind::Dir,
+            ..File::new(path, Vec::new())
+        }
+    }
+
+    pub fn mode(mut self, mode: u32) -> File {
+        self.mode = Some(mode);
+        self
+    }
+
+    pub fn executable(self) -> File {
+        self.mode(0o755)
+    }
+
+    pub fn on_conflict(mut self, policy: ConflictPolicy) -> File {
+        self.conflict = Some(policy);
+        self
+    }
+
+    pub fn on_duplicate(mut self, strategy: MergeStrategy) -> File {
+        self.merge = strategy;
+        self
+    }
+
+    pub fn policy(&self, default: ConflictPolicy) -> ConflictPolicy {
+        self.conflict.unwrap_or(default)
+    }
+
     #[cfg(feature = "fs")]
-    pub async fn write_to(&self, path: &std::path::Path, force: bool) -> Result<(), GeenieError> {
-        let file_path = self.path.to_logical_path(&path);
-        if async_fs::metadata(&file_path).await.is_ok() && !force {
-            return Err(GeenieError::exists(self.path.clone()));
+    pub async fn write_to<E: Asger>(
+        &self,
+        path: &std::path::Path,
+        policy: impl Into<ConflictPolicy>,
+        env: &mut Spurgt<E>,
+    ) -> Result<WriteOutcome, GeenieError> {
+        self.write_into(&FsTarget::new(path), policy, env).await
+    }
+
+    pub async fn write_into<T: Target, E: Asger>(
+        &self,
+        target: &T,
+        policy: impl Into<ConflictPolicy>,
+        env: &mut Spurgt<E>,
+    ) -> Result<WriteOutcome, GeenieError> {
+        let resolved = self.resolve(target, policy.into(), env).await?;
+        resolved.write(target).await?;
+        Ok(resolved.outcome)
+    }
+
+    async fn resolve<T: Target, E: Asger>(
+        &self,
+        target: &T,
+        policy: ConflictPolicy,
+        env: &mut Spurgt<E>,
+    ) -> Result<Resolved<'_>, GeenieError> {
+        match &self.kind {
+            FileKind::File => {}
+            FileKind::Dir if target.exists(&self.path).await? => {
+                return Ok(self.skipped(WriteOutcome::Unchanged))
+            }
+            FileKind::Dir => return Ok(self.resolved(WriteOutcome::Created)),
+            FileKind::Symlink(_) if !target.exists(&self.path).await? => {
+                return Ok(self.resolved(WriteOutcome::Created))
+            }
+            FileKind::Symlink(_) => {
+                return match self.policy(policy) {
+                    ConflictPolicy::Fail => Err(GeenieError::exists(self.path.clone())),
+                    ConflictPolicy::Overwrite => Ok(self.resolved(WriteOutcome::Overwritten)),
+                    _ => Ok(self.skipped(WriteOutcome::Skipped)),
+                }
+            }
         }
-        if let Some(parent) = file_path.parent() {
-            async_fs::create_dir_all(parent).await?;
+
+        let Some(existing) = target.read(&self.path).await? else {
+            return Ok(self.resolved(WriteOutcome::Created));
+        };
+
+        if existing == self.content {
+            return Ok(self.skipped(WriteOutcome::Unchanged));
+        }
+
+        let policy = match self.policy(policy) {
+            ConflictPolicy::Ask => self.ask(&existing, env).await?,
+            policy => policy,
+        };
+
+        match policy {
+            ConflictPolicy::Skip | ConflictPolicy::Ask => Ok(self.skipped(WriteOutcome::Skipped)),
+            ConflictPolicy::Overwrite => Ok(self.resolved(WriteOutcome::Overwritten)),
+            ConflictPolicy::Fail => Err(GeenieError::exists(self.path.clone())),
+            ConflictPolicy::Sidecar => Ok(self.sidecar()),
+            ConflictPolicy::Merge => {
+                let (Ok(current), Ok(generated)) = (
+                    core::str::from_utf8(&existing),
+                    core::str::from_utf8(&self.content),
+                ) else {
+                    return Ok(self.sidecar());
+                };
+
+                let (merged, conflicts) = crate::diff::merge(current, generated);
+
+                Ok(Resolved {
+                    content: Some(Cow::Owned(merged.into_bytes())),
+                    ..self.resolved(WriteOutcome::Merged { conflicts })
+                })
+            }
+        }
+    }
+
+    async fn ask<E: Asger>(
+        &self,
+        existing: &[u8],
+        env: &mut Spurgt<E>,
+    ) -> Result<ConflictPolicy, GeenieError> {
+        let diff = crate::diff::diff(&self.path, existing, &self.content);
+        env.info(&diff.to_string())
+            .await
+            .map_err(GeenieError::backend)?;
+
+        if env
+            .ask(confirm(format!("Overwrite {}?", self.path)))
+            .await?
+        {
+            return Ok(ConflictPolicy::Overwrite);
+        }
+
+        let sidecar = sidecar_path(&self.path);
+        if env
+            .ask(confirm(format!("Keep both and write {}?", sidecar)))
+            .await?
+        {
+            return Ok(ConflictPolicy::Sidecar);
+        }
+
+        Ok(ConflictPolicy::Skip)
+    }
+
+    fn resolved(&self, outcome: WriteOutcome) -> Resolved<'_> {
+        Resolved {
+            file: self,
+            dest: self.path.clone(),
+            content: Some(Cow::Borrowed(&self.content)),
+            outcome,
         }
-        async_fs::write(file_path, &self.content).await?;
+    }
 
-        Ok(())
+    fn skipped(&self, outcome: WriteOutcome) -> Resolved<'_> {
+        Resolved {
+            content: None,
+            ..self.resolved(outcome)
+        }
+    }
+
+    fn sidecar(&self) -> Resolved<'_> {
+        let dest = sidecar_path(&self.path);
+        Resolved {
+            dest: dest.clone(),
+            ..self.resolved(WriteOutcome::Sidecar(dest))
+        }
+    }
+
+    pub async fn plan<T: Target>(
+        &self,
+        target: &T,
+        policy: ConflictPolicy,
+    ) -> Result<PlannedFile, GeenieError> {
+        let existing = match self.kind {
+            FileKind::File => target.read(&self.path).await?,
+            FileKind::Symlink(_) | FileKind::Dir => target.exists(&self.path).await?.then(Vec::new),
+        };
+
+        let Some(existing) = existing else {
+            return Ok(self.planned(FileAction::Create));
+        };
+
+        let action = match (&self.kind, self.policy(policy)) {
+            (FileKind::Dir, _) => FileAction::Unchanged,
+            (FileKind::File, _) if existing == self.content => FileAction::Unchanged,
+            (_, ConflictPolicy::Overwrite) => FileAction::Overwrite,
+            (_, ConflictPolicy::Fail) => FileAction::Conflict,
+            (FileKind::Symlink(_), _) | (_, ConflictPolicy::Skip) => FileAction::Skip,
+            (_, ConflictPolicy::Sidecar) => FileAction::Sidecar,
+            (_, ConflictPolicy::Merge)
+                if core::str::from_utf8(&existing).is_err()
+                    || core::str::from_utf8(&self.content).is_err() =>
+            {
+                FileAction::Sidecar
+            }
+            (_, ConflictPolicy::Merge) => FileAction::Merge,
+            (_, ConflictPolicy::Ask) => FileAction::Ask,
+        };
+
+        Ok(self.planned(action))
+    }
+
+    fn planned(&self, action: FileAction) -> PlannedFile {
+        PlannedFile {
+            path: self.path.clone(),
+            action,
+        }
+    }
+
+    pub async fn diff<T: Target>(&self, target: &T) -> Result<Option<FileDiff>, GeenieError> {
+        if self.kind != FileKind::File {
+            return Ok(None);
+        }
+
+        let Some(existing) = target.read(&self.path).await? else {
+            return Ok(None);
+        };
+
+        Ok(Some(crate::diff::diff(
+            &self.path,
+            &existing,
+            &self.content,
+        )))
+    }
+}
+
+fn sidecar_path(path: &relative_path::RelativePath) -> RelativePathBuf {
+    RelativePathBuf::from(format!("{path}.new"))
+}
+
+struct Resolved<'a> {
+    file: &'a File,
+    dest: RelativePathBuf,
+    content: Option<Cow<'a, [u8]>>,
+    outcome: WriteOutcome,
+}
+
+impl Resolved<'_> {
+    async fn write<T: Target>(&self, target: &T) -> Result<(), GeenieError> {
+        let Some(content) = &self.content else {
+            return match (self.file.mode, &self.outcome) {
+                (Some(mode), WriteOutcome::Unchanged) => target.set_mode(&self.dest, mode).await,
+                _ => Ok(()),
+            };
+        };
+
+        match (&self.file.kind, self.file.mode) {
+            (FileKind::File, Some(mode)) => target.write_mode(&self.dest, content, mode).await,
+            (FileKind::File, None) => target.write(&self.dest, content).await,
+            (FileKind::Symlink(link), _) => target.symlink(&self.dest, link).await,
+            (FileKind::Dir, Some(mode)) => target.create_dir_mode(&self.dest, mode).await,
+            (FileKind::Dir, None) => target.create_dir(&self.dest).await,
+        }
     }
 }
 
@@ -54,34 +322,60 @@ pub struct FileList {
 
 impl FileList {
     #[cfg(feature = "fs")]
-    pub async fn write_to(
+    pub async fn write_to<E: Asger>(
         &self,
         path: impl AsRef<std::path::Path>,
-        force: bool,
-    ) -> Result<(), GeenieError> {
-        let path = path.as_ref();
-        for files in self.files.chunks(10) {
+        policy: impl Into<ConflictPolicy>,
+        env: &mut Spurgt<E>,
+    ) -> Result<WriteReport, GeenieError> {
+        self.write_into(&FsTarget::new(path.as_ref()), policy, env)
+            .await
+    }
+
+    pub async fn write_into<T: Target, E: Asger>(
+        &self,
+        target: &T,
+        policy: impl Into<ConflictPolicy>,
+        env: &mut Spurgt<E>,
+    ) -> Result<WriteReport, GeenieError> {
+        let policy = policy.into();
+
+        let mut resolved = Vec::with_capacity(self.files.len());
+        for file in &self.files {
+            resolved.push(file.resolve(target, policy, env).await?);
+        }
+
+        for files in resolved.chunks(10) {
             let mut futures = futures::stream::FuturesUnordered::new();
 
             for file in files {
-                futures.push(async move { file.write_to(path, force).await });
+                futures.push(async move { file.write(target).await });
             }
 
             while let Some(next) = futures.next().await {
-                match next {
-                    Ok(e) => {
-                        let _ = e;
-                    }
-                    Err(err) => {
-                        if err.is_io() {
-                            return Err(err);
-                        }
-                    }
-                }
+                next?;
             }
         }
 
-        Ok(())
+        let mut report = WriteReport::default();
+        for file in resolved {
+            report.push(file.file.path.clone(), file.outcome);
+        }
+
+        Ok(report)
+    }
+
+    pub async fn plan<T: Target>(
+        &self,
+        target: &T,
+        policy: impl Into<ConflictPolicy>,
+    ) -> Result<Vec<PlannedFile>, GeenieError> {
+        let policy = policy.into();
+        let mut planned = Vec::with_capacity(self.files.len());
+        for file in &self.files {
+            planned.push(file.plan(target, policy).await?);
+        }
+        Ok(planned)
     }
 
     pub fn push(&mut self, file: File) {
@@ -133,3 +427,218 @@ impl From<FileList> for Vec<File> {
         files.files
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use relative_path::RelativePath;
+
+    use crate::target::MemoryTarget;
+
+    fn action(file: &File, target: &MemoryTarget, policy: ConflictPolicy) -> FileAction {
+        block_on(file.plan(target, policy)).unwrap().action
+    }
+
+    #[test]
+    fn plan_maps_policies_to_actions() {
+        let target = MemoryTarget::new().with_file("a.txt", "old");
+        let file = File::new("a.txt", "new");
+
+        assert_eq!(
+            action(&file, &target, ConflictPolicy::Overwrite),
+            FileAction::Overwrite
+        );
+        assert_eq!(
+            action(&file, &target, ConflictPolicy::Fail),
+            FileAction::Conflict
+        );
+        assert_eq!(
+            action(&file, &target, ConflictPolicy::Skip),
+            FileAction::Skip
+        );
+        assert_eq!(
+            action(&file, &target, ConflictPolicy::Sidecar),
+            FileAction::Sidecar
+        );
+        assert_eq!(
+            action(&file, &target, ConflictPolicy::Merge),
+            FileAction::Merge
+        );
+        assert_eq!(action(&file, &target, ConflictPolicy::Ask), FileAction::Ask);
+    }
+
+    #[test]
+    fn plan_prefers_file_policy() {
+        let target = MemoryTarget::new().with_file("a.txt", "old");
+        let file = File::new("a.txt", "new").on_conflict(ConflictPolicy::Skip);
+
+        assert_eq!(
+            action(&file, &target, ConflictPolicy::Fail),
+            FileAction::Skip
+        );
+    }
+
+    #[test]
+    fn plan_reports_unchanged_and_created() {
+        let target = MemoryTarget::new().with_file("a.txt", "same");
+
+        assert_eq!(
+            action(&File::new("a.txt", "same"), &target, ConflictPolicy::Fail),
+            FileAction::Unchanged
+        );
+        assert_eq!(
+            action(&File::new("b.txt", "new"), &target, ConflictPolicy::Fail),
+            FileAction::Create
+        );
+    }
+
+    #[test]
+    fn plan_merges_binary_into_sidecar() {
+        let target = MemoryTarget::new().with_file("a.bin", vec![0xff, 0xfe]);
+        let file = File::new("a.bin", vec![0xff, 0x00]);
+
+        assert_eq!(
+            action(&file, &target, ConflictPolicy::Merge),
+            FileAction::Sidecar
+        );
+    }
+
+    #[test]
+    fn plan_handles_dirs_and_symlinks() {
+        let target = MemoryTarget::new().with_file("src/lib.rs", "");
+        block_on(target.symlink(RelativePath::new("link"), "src")).unwrap();
+
+        assert_eq!(
+            action(&File::dir("src"), &target, ConflictPolicy::Fail),
+            FileAction::Unchanged
+        );
+        assert_eq!(
+            action(
+                &File::symlink("link", "src"),
+                &target,
+                ConflictPolicy::Merge
+            ),
+            FileAction::Skip
+        );
+        assert_eq!(
+            action(&File::symlink("link", "src"), &target, ConflictPolicy::Fail),
+            FileAction::Conflict
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn ask_shows_the_diff_before_overwriting() {
+        use crate::testing::TestEnv;
+
+        let target = MemoryTarget::new().with_file("a.txt", "old\n");
+        let env = TestEnv::new().expect_confirm("Overwrite a.txt?", true);
+        let mut spurgt = Spurgt::new(env.clone());
+
+        let outcome = block_on(File::new("a.txt", "new\n").write_into(
+            &target,
+            ConflictPolicy::Ask,
+            &mut spurgt,
+        ))
+        .unwrap();
+
+        assert_eq!(outcome, WriteOutcome::Overwritten);
+        assert_eq!(target.get("a.txt").unwrap(), b"new\n");
+        assert_eq!(
+            env.log()[0],
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-old\n+new\n"
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    fn write(files: Vec<File>, target: &MemoryTarget) -> Result<WriteReport, GeenieError> {
+        let mut env = Spurgt::new(crate::testing::TestEnv::new());
+        block_on(FileList::from(files).write_into(target, ConflictPolicy::Fail, &mut env))
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn write_reports_each_outcome() {
+        let target = MemoryTarget::new()
+            .with_file("same.txt", "same")
+            .with_file("skip.txt", "old")
+            .with_file("over.txt", "old")
+            .with_file("side.txt", "old")
+            .with_file("merge.txt", "a\nc\n")
+            .with_file("clash.txt", "a\nb\n");
+
+        let report = write(
+            vec![
+                File::new("new.txt", "new"),
+                File::new("same.txt", "same"),
+                File::new("skip.txt", "new").on_conflict(ConflictPolicy::Skip),
+                File::new("over.txt", "new").on_conflict(ConflictPolicy::Overwrite),
+                File::new("side.txt", "new").on_conflict(ConflictPolicy::Sidecar),
+                File::new("merge.txt", "a\nb\nc\n").on_conflict(ConflictPolicy::Merge),
+                File::new("clash.txt", "a\nB\n").on_conflict(ConflictPolicy::Merge),
+            ],
+            &target,
+        )
+        .unwrap();
+
+        let outcomes = report
+            .files
+            .iter()
+            .map(|file| (file.path.as_str(), file.outcome.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outcomes,
+            vec![
+                ("new.txt", WriteOutcome::Created),
+                ("same.txt", WriteOutcome::Unchanged),
+                ("skip.txt", WriteOutcome::Skipped),
+                ("over.txt", WriteOutcome::Overwritten),
+                ("side.txt", WriteOutcome::Sidecar("side.txt.new".into())),
+                ("merge.txt", WriteOutcome::Merged { conflicts: false }),
+                ("clash.txt", WriteOutcome::Merged { conflicts: true }),
+            ]
+        );
+        assert_eq!(report.conflicts().count(), 1);
+
+        assert_eq!(target.get("new.txt").unwrap(), b"new");
+        assert_eq!(target.get("skip.txt").unwrap(), b"old");
+        assert_eq!(target.get("over.txt").unwrap(), b"new");
+        assert_eq!(target.get("side.txt").unwrap(), b"old");
+        assert_eq!(target.get("side.txt.new").unwrap(), b"new");
+        assert_eq!(target.get("merge.txt").unwrap(), b"a\nb\nc\n");
+        assert!(String::from_utf8(target.get("clash.txt").unwrap())
+            .unwrap()
+            .contains("<<<<<<< current\nb\n=======\nB\n>>>>>>> generated\n"));
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn write_fails_before_touching_the_target() {
+        let target = MemoryTarget::new().with_file("a.txt", "old");
+
+        let err = write(
+            vec![File::new("b.txt", "new"), File::new("a.txt", "new")],
+            &target,
+        )
+        .unwrap_err();
+
+        assert!(matches!(err, GeenieError::Exists { path } if path == "a.txt"));
+        assert_eq!(target.files().len(), 1);
+        assert_eq!(target.get("a.txt").unwrap(), b"old");
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn plan_reports_read_errors() {
+        let root = std::env::temp_dir().join(format!("geenie-plan-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("config")).unwrap();
+
+        let result =
+            block_on(File::new("config", "x").plan(&FsTarget::new(&root), ConflictPolicy::Skip));
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(matches!(result, Err(GeenieError::Io(_))));
+    }
+}
@@ -1,6 +1,8 @@
 use core::{future::Future, pin::Pin};
+use std::path::{Path, PathBuf};
 
 use crate::{
+    answers::{Answers, AnswersEnv, Session},
     command::{Command, CommandItem},
     item::{DynamicItem, ItemBox},
     result::{GeenieResult, ResultBuilder},
@@ -11,6 +13,8 @@ use spurgt::Spurgt;
 pub struct Geenie<E, C> {
     env: Spurgt<E>,
     items: Vec<Box<dyn DynamicItem<E, C>>>,
+    answers: Answers,
+    root: Option<PathBuf>,
 }
 
 impl<E, C> Default for Geenie<E, C>
@@ -21,6 +25,8 @@ where
         Geenie {
             env: Spurgt::default(),
             items: Default::default(),
+            answers: Answers::default(),
+            root: None,
         }
     }
 }
@@ -30,13 +36,40 @@ impl<E: spurgt::core::Env, C> Geenie<E, C> {
         Geenie {
             env: Spurgt::new(env),
             items: Default::default(),
+            answers: Answers::default(),
+            root: None,
         }
     }
 
+    /// Answer questions from `answers` and record every answer given, also
+    /// of questions asked directly on the env, into the result's session.
+    pub fn with_answers(env: E, answers: Answers) -> Geenie<AnswersEnv<E>, C> {
+        let mut geenie = Geenie::new(AnswersEnv::new(env, answers.share()));
+        geenie.answers = answers;
+        geenie
+    }
+
     pub fn env(&mut self) -> &mut Spurgt<E> {
         &mut self.env
     }
 
+    pub fn answers(&mut self, answers: Answers) -> &mut Self {
+        self.answers.replace(answers);
+        self
+    }
+
+    pub fn root(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.root = Some(path.into());
+        self
+    }
+
+    /// Answer questions asked through [`Context::ask`] from a recorded
+    /// session. Questions asked directly on the env are only answered by an
+    /// env that knows the session, see [`Geenie::with_answers`].
+    pub fn replay(&mut self, session: Session) -> &mut Self {
+        self.answers(session.replay())
+    }
+
     pub fn push<T>(&mut self, item: T) -> &mut Self
     where
         T: Item<E, C> + 'static,
@@ -45,6 +78,15 @@ impl<E: spurgt::core::Env, C> Geenie<E, C> {
         self
     }
 
+    #[cfg(feature = "process")]
+    pub fn prerequisites(&mut self, prerequisites: crate::Prerequisites) -> &mut Self
+    where
+        E: spurgt::Asger + 'static,
+    {
+        self.items.insert(0, Box::new(ItemBox(prerequisites)));
+        self
+    }
+
     pub fn command<T>(&mut self, command: T) -> &mut Self
     where
         T: Command<E> + 'static,
@@ -60,11 +102,20 @@ impl<E: spurgt::core::Env, C> Geenie<E, C> {
 
     pub async fn run(mut self, context: &mut C) -> Result<GeenieResult<E>, GeenieError> {
         let mut files = ResultBuilder::<E>::default();
+        let root = self.root.as_deref();
         for item in self.items {
-            Self::process_item(&mut self.env, item, &mut files, context).await?;
+            Self::process_item(
+                &mut self.env,
+                item,
+                &mut files,
+                context,
+                &mut self.answers,
+                root,
+            )
+            .await?;
         }
 
-        Ok(files.build(self.env))
+        Ok(files.build(self.env, self.answers.into_session()))
     }
 
     fn process_item<'a>(
@@ -72,6 +123,8 @@ impl<E: spurgt::core::Env, C> Geenie<E, C> {
         item: Box<dyn DynamicItem<E, C>>,
         files: &'a mut ResultBuilder<E>,
         context: &'a mut C,
+        answers: &'a mut Answers,
+        root: Option<&'a Path>,
     ) -> Pin<Box<dyn Future<Output = Result<(), GeenieError>> + 'a>>
     where
         C: 'a,
@@ -84,13 +137,16 @@ impl<E: spurgt::core::Env, C> Geenie<E, C> {
                     files,
                     questions: &mut questions,
                     ctx: context,
+                    answers,
+                    root,
+                    outer: None,
                 },
                 env,
             )
             .await?;
 
             for question in questions {
-                Self::process_item(env, question, files, context).await?;
+                Self::process_item(env, question, files, context, answers, root).await?;
             }
 
             Ok(())
@@ -101,7 +157,7 @@ impl<E: spurgt::core::Env, C> Geenie<E, C> {
 impl<E, C> Item<E, C> for Geenie<E, C> {
     fn process<'a>(
         self,
-        ctx: Context<'a, E, C>,
+        mut ctx: Context<'a, E, C>,
         env: &'a mut Spurgt<E>,
     ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
         async move {
@@ -111,6 +167,12 @@ impl<E, C> Item<E, C> for Geenie<E, C> {
                         files: ctx.files,
                         questions: ctx.questions,
                         ctx: ctx.ctx,
+                        answers: ctx.answers,
+                        root: ctx.root,
+                        outer: match &mut ctx.outer {
+                            Some(outer) => Some(&mut **outer),
+                            None => None,
+                        },
                     },
                     env,
                 )
@@ -0,0 +1,383 @@
+use regex::Regex;
+use relative_path::RelativePathBuf;
+use spurgt::Spurgt;
+
+use crate::{Context, File, GeenieError, Item};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Anchor {
+    Text(String),
+    Regex(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Position {
+    After(Anchor),
+    Before(Anchor),
+    ModList,
+    Start,
+    End,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inject {
+    path: RelativePathBuf,
+    content: String,
+    position: Position,
+    last: bool,
+    marker: Option<String>,
+}
+
+impl Inject {
+    pub fn new(path: impl Into<RelativePathBuf>, content: impl ToString) -> Inject {
+        Inject {
+            path: path.into(),
+            content: content.to_string(),
+            position: Position::End,
+            last: false,
+            marker: None,
+        }
+    }
+
+    pub fn after(mut self, anchor: impl ToString) -> Self {
+        self.position = Position::After(Anchor::Text(anchor.to_string()));
+        self
+    }
+
+    pub fn before(mut self, anchor: impl ToString) -> Self {
+        self.position = Position::Before(Anchor::Text(anchor.to_string()));
+        self
+    }
+
+    pub fn after_regex(mut self, pattern: impl ToString) -> Self {
+        self.position = Position::After(Anchor::Regex(pattern.to_string()));
+        self
+    }
+
+    pub fn before_regex(mut self, pattern: impl ToString) -> Self {
+        self.position = Position::Before(Anchor::Regex(pattern.to_string()));
+        self
+    }
+
+    pub fn mod_list(mut self) -> Self {
+        self.position = Position::ModList;
+        self
+    }
+
+    pub fn at_start(mut self) -> Self {
+        self.position = Position::Start;
+        self
+    }
+
+    pub fn at_end(mut self) -> Self {
+        self.position = Position::End;
+        self
+    }
+
+    pub fn last(mut self) -> Self {
+        self.last = true;
+        self
+    }
+
+    pub fn skip_if(mut self, marker: impl ToString) -> Self {
+        self.marker = Some(marker.to_string());
+        self
+    }
+
+    pub fn apply(&self, existing: &str) -> Result<File, GeenieError> {
+        let newline = if existing.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+
+        let (offset, indent) = self.position(existing)?;
+
+        if self.is_present(existing, offset) {
+            return Ok(self.file(existing.to_string()));
+        }
+
+        let mut content = existing[..offset].to_string();
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push_str(newline);
+        }
+
+        content.push_str(&reindent(&self.content, &indent, newline));
+        content.push_str(newline);
+        content.push_str(&existing[offset..]);
+
+        Ok(self.file(content))
+    }
+
+    fn position(&self, existing: &str) -> Result<(usize, String), GeenieError> {
+        let position = match &self.position {
+            Position::Start => (0, String::new()),
+            Position::End => (existing.len(), String::new()),
+            Position::ModList => {
+                let pattern = Regex::new(r"(?m)^([ \t]*)(pub(\([^)]*\))?\s+)?mod\s+\w+\s*;[^\n]*$")
+                    .expect("mod pattern is valid");
+                match pattern.captures_iter(existing).last() {
+                    Some(captures) => (
+                        line_end(existing, captures.get(0).expect("whole match").end()),
+                        captures[1].to_string(),
+                    ),
+                    None => (existing.len(), String::new()),
+                }
+            }
+            Position::Before(anchor) => {
+                let (start, _) = self.find(existing, anchor)?;
+                let start = line_start(existing, start);
+                (start, indentation(&existing[start..]).to_string())
+            }
+            Position::After(anchor) => {
+                let (start, end) = self.find(existing, anchor)?;
+                let end = if existing[start..end].ends_with('\n') {
+                    end
+                } else {
+                    line_end(existing, end)
+                };
+                let line = &existing[line_start(existing, start)..end];
+                let indent = match existing[end..].lines().find(|line| !line.trim().is_empty()) {
+                    Some(next) if opens_block(line) => indentation(next),
+                    _ => indentation(line),
+                };
+                (end, indent.to_string())
+            }
+        };
+
+        Ok(position)
+    }
+
+    fn file(&self, content: String) -> File {
+        File::new(self.path.clone(), content)
+    }
+
+    /// Whether the snippet was injected before: the marker is in the file or
+    /// the snippet already sits right at the insertion point.
+    fn is_present(&self, existing: &str, offset: usize) -> bool {
+        if let Some(marker) = &self.marker {
+            return existing.contains(marker.as_str());
+        }
+
+        let needle = significant(&self.content);
+        if needle.is_empty() {
+            return true;
+        }
+
+        significant(&existing[..offset]).ends_with(&needle)
+            || significant(&existing[offset..]).starts_with(&needle)
+    }
+
+    fn find(&self, existing: &str, anchor: &Anchor) -> Result<(usize, usize), GeenieError> {
+        let found = match anchor {
+            Anchor::Text(text) => {
+                let found = if self.last {
+                    existing.rfind(text.as_str())
+                } else {
+                    existing.find(text.as_str())
+                };
+                found.map(|start| (start, start + text.len()))
+            }
+            Anchor::Regex(pattern) => {
+                let regex = Regex::new(pattern).map_err(GeenieError::backend)?;
+                let found = if self.last {
+                    regex.find_iter(existing).last()
+                } else {
+                    regex.find(existing)
+                };
+                found.map(|found| (found.start(), found.end()))
+            }
+        };
+
+        found.ok_or_else(|| {
+            let anchor = match anchor {
+                Anchor::Text(text) | Anchor::Regex(text) => text,
+            };
+            GeenieError::anchor_not_found(self.path.clone(), anchor)
+        })
+    }
+}
+
+impl<E, C> Item<E, C> for Inject {
+    fn process<'a>(
+        self,
+        mut ctx: Context<'a, E, C>,
+        _env: &'a mut Spurgt<E>,
+    ) -> impl std::future::Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            let existing = ctx.require_existing(&self.path).await?;
+            let file = self.apply(&String::from_utf8_lossy(&existing.content))?;
+            ctx.file(File {
+                content: file.content,
+                ..existing
+            })?;
+            Ok(())
+        }
+    }
+}
+
+pub fn inject(path: impl Into<RelativePathBuf>, content: impl ToString) -> Inject {
+    Inject::new(path, content)
+}
+
+fn line_start(text: &str, offset: usize) -> usize {
+    text[..offset]
+        .rfind('\n')
+        .map(|index| index + 1)
+        .unwrap_or(0)
+}
+
+fn line_end(text: &str, offset: usize) -> usize {
+    text[offset..]
+        .find('\n')
+        .map(|index| offset + index + 1)
+        .unwrap_or(text.len())
+}
+
+fn indentation(line: &str) -> &str {
+    let line = line.lines().next().unwrap_or_default();
+    &line[..line.len() - line.trim_start().len()]
+}
+
+fn opens_block(line: &str) -> bool {
+    line.trim_end().ends_with(['{', '[', '('])
+}
+
+fn significant(text: &str) -> Vec<&str> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+fn common_prefix<'a>(a: &'a str, b: &str) -> &'a str {
+    let len = a
+        .char_indices()
+        .zip(b.chars())
+        .find(|((_, a), b)| a != b)
+        .map(|((index, _), _)| index)
+        .unwrap_or(a.len().min(b.len()));
+    &a[..len]
+}
+
+fn reindent(content: &str, indent: &str, newline: &str) -> String {
+    let common = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(indentation)
+        .reduce(common_prefix)
+        .unwrap_or_default();
+
+    content
+        .trim_matches('\n')
+        .lines()
+        .map(|line| {
+            if line.trim().is_empty() {
+                String::new()
+            } else {
+                format!("{indent}{}", line.strip_prefix(common).unwrap_or(line))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(newline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(existing: &str, inject: Inject) -> String {
+        String::from_utf8(inject.apply(existing).unwrap().content).unwrap()
+    }
+
+    #[test]
+    fn injects_at_each_position() {
+        let source = "fn main() {\n    run();\n}\n";
+
+        assert_eq!(
+            apply(source, inject("main.rs", "setup();").after("fn main() {")),
+            "fn main() {\n    setup();\n    run();\n}\n"
+        );
+        assert_eq!(
+            apply(source, inject("main.rs", "done();").before("}")),
+            "fn main() {\n    run();\ndone();\n}\n"
+        );
+        assert_eq!(
+            apply(source, inject("main.rs", "use app;").at_start()),
+            "use app;\nfn main() {\n    run();\n}\n"
+        );
+        assert_eq!(apply("a", inject("a.txt", "b").at_end()), "a\nb\n");
+        assert_eq!(
+            apply("a\nb\na\n", inject("a.txt", "c").after("a").last()),
+            "a\nb\na\nc\n"
+        );
+        assert_eq!(
+            apply(
+                "x = 1\ny = 2\n",
+                inject("a.txt", "z = 3").after_regex(r"y = \d")
+            ),
+            "x = 1\ny = 2\nz = 3\n"
+        );
+        assert_eq!(
+            apply("a\r\nb\r\n", inject("a.txt", "c\nd").before("b")),
+            "a\r\nc\r\nd\r\nb\r\n"
+        );
+    }
+
+    #[test]
+    fn appends_to_the_mod_list() {
+        assert_eq!(
+            apply(
+                "//! docs\n\nmod a;\npub(crate) mod b;\n\nfn f() {}\n",
+                inject("lib.rs", "mod c;").mod_list()
+            ),
+            "//! docs\n\nmod a;\npub(crate) mod b;\nmod c;\n\nfn f() {}\n"
+        );
+        assert_eq!(
+            apply("fn f() {}\n", inject("lib.rs", "mod c;").mod_list()),
+            "fn f() {}\nmod c;\n"
+        );
+    }
+
+    #[test]
+    fn is_idempotent_at_the_insertion_point() {
+        let source = "mod a;\n\nfn f() {\n    if x {\n    }\n}\n";
+        let once = apply(source, inject("lib.rs", "mod b;").mod_list());
+        assert_eq!(apply(&once, inject("lib.rs", "mod b;").mod_list()), once);
+
+        // The same line elsewhere in the file does not count.
+        assert_eq!(
+            apply(source, inject("lib.rs", "}").before("fn f")),
+            "mod a;\n\n}\nfn f() {\n    if x {\n    }\n}\n"
+        );
+        assert_eq!(
+            apply(source, inject("lib.rs", "mod a;").at_end()),
+            "mod a;\n\nfn f() {\n    if x {\n    }\n}\nmod a;\n"
+        );
+
+        let marked = inject("lib.rs", "mod a;").at_end().skip_if("mod a");
+        assert_eq!(apply(source, marked), source);
+    }
+
+    #[test]
+    fn reindents_by_the_common_prefix() {
+        assert_eq!(
+            reindent("\t\tif x {\n\t\t\ty();\n\n\t\t}\n", "    ", "\n"),
+            "    if x {\n    \ty();\n\n    }"
+        );
+        assert_eq!(
+            reindent("\u{a0}\u{a0}a\n\u{a0} b\n", "", "\n"),
+            "\u{a0}a\n b"
+        );
+        assert_eq!(reindent("  a\n\tb\n", ">", "\n"), ">  a\n>\tb");
+    }
+
+    #[test]
+    fn reports_missing_anchors() {
+        assert!(matches!(
+            inject("a.txt", "x").after("missing").apply("a\n"),
+            Err(GeenieError::AnchorNotFound { .. })
+        ));
+        assert!(inject("a.txt", "x").after_regex("(").apply("a\n").is_err());
+    }
+}
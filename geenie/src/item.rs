@@ -1,7 +1,10 @@
 use relative_path::RelativePathBuf;
-use spurgt::Spurgt;
+use spurgt::{Asger, Spurgt};
 
-use crate::{result::ResultBuilder, Context, File, GeenieError};
+use crate::{
+    combinator::{FilterFiles, MapFiles, Optional, OrElse, Then, When},
+    Context, File, GeenieError,
+};
 use core::{future::Future, pin::Pin};
 
 pub trait Item<E, C> {
@@ -37,6 +40,76 @@ pub trait ItemExt<E, C>: Item<E, C> {
             mount: path.into(),
         }
     }
+
+    /// Process the item only if the predicate holds for the context.
+    fn when<F>(self, predicate: F) -> impl Item<E, C>
+    where
+        Self: Sized + 'static,
+        F: FnOnce(&C) -> bool + 'static,
+    {
+        When::new(self, predicate, true)
+    }
+
+    fn unless<F>(self, predicate: F) -> impl Item<E, C>
+    where
+        Self: Sized + 'static,
+        F: FnOnce(&C) -> bool + 'static,
+    {
+        When::new(self, predicate, false)
+    }
+
+    /// Process the fallback instead if the item fails because of an external
+    /// tool, see [`GeenieError::is_tool_failure`]. Anything the item did is
+    /// discarded first. Other errors are returned as is.
+    ///
+    /// An item skipped by [`ItemExt::when`] did not fail, so
+    /// `a.when(p).or_else(b)` never runs `b` when `p` is false. Use
+    /// `b.unless(p)` for that branch.
+    fn or_else<T>(self, fallback: T) -> impl Item<E, C>
+    where
+        Self: Sized + 'static,
+        T: Item<E, C> + 'static,
+        E: 'static,
+        C: Clone + 'static,
+    {
+        OrElse::new(self, fallback)
+    }
+
+    fn optional(self, id: impl ToString, prompt: impl ToString) -> impl Item<E, C>
+    where
+        Self: Sized + 'static,
+        E: Asger,
+    {
+        Optional::new(self, id, prompt)
+    }
+
+    fn map_files<F>(self, map: F) -> impl Item<E, C>
+    where
+        Self: Sized + 'static,
+        F: FnMut(File) -> File + Clone + 'static,
+        E: 'static,
+        C: 'static,
+    {
+        MapFiles::new(self, map)
+    }
+
+    fn filter_files<F>(self, filter: F) -> impl Item<E, C>
+    where
+        Self: Sized + 'static,
+        F: FnMut(&File) -> bool + Clone + 'static,
+        E: 'static,
+        C: 'static,
+    {
+        FilterFiles::new(self, filter)
+    }
+
+    fn then<T>(self, next: T) -> impl Item<E, C>
+    where
+        Self: Sized + 'static,
+        T: Item<E, C> + 'static,
+    {
+        Then::new(self, next)
+    }
 }
 
 impl<T, E, C> ItemExt<E, C> for T where T: Item<E, C> {}
@@ -100,32 +173,23 @@ where
         env: &'a mut Spurgt<E>,
     ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
         async move {
-            let mut files = ResultBuilder::default();
-            let mut items = Vec::default();
-
-            self.item
-                .process(
-                    Context {
-                        files: &mut files,
-                        questions: &mut items,
-                        ctx: ctx.ctx,
-                    },
-                    env,
-                )
+            let root = ctx.root.map(|root| self.mount.to_logical_path(root));
+            let isolated = ctx
+                .isolate_in(self.item, env, root.as_deref(), &self.mount)
                 .await?;
 
-            for file in files.files {
+            for file in isolated.files {
                 ctx.file(File {
-                    path: self.mount.join(file.path),
-                    content: file.content,
+                    path: self.mount.join(&file.path),
+                    ..file
                 })?;
             }
 
-            for cmd in files.commands {
+            for cmd in isolated.commands {
                 ctx.files.push_command(cmd);
             }
 
-            for item in items {
+            for item in isolated.items {
                 ctx.push(MountItem {
                     item: ItemBox(item),
                     mount: self.mount.clone(),
@@ -0,0 +1,479 @@
+use relative_path::RelativePathBuf;
+use serde::{Deserialize, Serialize};
+use serde_json::{ser::PrettyFormatter, Map, Value};
+use spurgt::Spurgt;
+
+use crate::{merge::merge_json, Context, File, GeenieError, Item};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Edit {
+    Merge(Value),
+    Set(String, Value),
+    Patch(Value),
+    /// A value that could not be represented as json.
+    Invalid(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonEdit {
+    path: RelativePathBuf,
+    edits: Vec<Edit>,
+}
+
+impl JsonEdit {
+    pub fn new(path: impl Into<RelativePathBuf>) -> JsonEdit {
+        JsonEdit {
+            path: path.into(),
+            edits: Vec::new(),
+        }
+    }
+
+    pub fn merge(mut self, value: impl Serialize) -> Self {
+        self.edits.push(to_edit(value, Edit::Merge));
+        self
+    }
+
+    pub fn set(mut self, pointer: impl ToString, value: impl Serialize) -> Self {
+        let pointer = pointer.to_string();
+        self.edits
+            .push(to_edit(value, |value| Edit::Set(pointer, value)));
+        self
+    }
+
+    pub fn patch(mut self, operations: impl Serialize) -> Self {
+        self.edits.push(to_edit(operations, Edit::Patch));
+        self
+    }
+
+    pub fn apply(&self, existing: Option<&[u8]>) -> Result<File, GeenieError> {
+        let source = existing
+            .map(|content| String::from_utf8_lossy(content).into_owned())
+            .unwrap_or_default();
+
+        let mut document = if source.trim().is_empty() {
+            Value::Object(Map::new())
+        } else {
+            serde_json::from_str(&source).map_err(|err| {
+                GeenieError::backend(format!("could not parse {}: {err}", self.path))
+            })?
+        };
+
+        for edit in &self.edits {
+            match edit {
+                Edit::Merge(value) => merge_json(&mut document, value.clone()),
+                Edit::Set(pointer, value) => set(&mut document, pointer, value.clone())?,
+                Edit::Patch(operations) => {
+                    let operations = match operations {
+                        Value::Array(_) => Vec::<PatchOperation>::deserialize(operations),
+                        _ => PatchOperation::deserialize(operations).map(|op| vec![op]),
+                    }
+                    .map_err(|err| GeenieError::backend(format!("invalid json patch: {err}")))?;
+
+                    for operation in operations {
+                        patch(&mut document, operation)?;
+                    }
+                }
+                Edit::Invalid(err) => {
+                    return Err(GeenieError::backend(format!(
+                        "invalid json value for {}: {err}",
+                        self.path
+                    )))
+                }
+            }
+        }
+
+        let indent = detect_indent(&source);
+        let mut content = Vec::new();
+        let mut serializer = serde_json::Serializer::with_formatter(
+            &mut content,
+            PrettyFormatter::with_indent(indent.as_bytes()),
+        );
+        document
+            .serialize(&mut serializer)
+            .map_err(GeenieError::backend)?;
+
+        if existing.is_none() || source.ends_with('\n') {
+            content.push(b'\n');
+        }
+
+        Ok(File::new(self.path.clone(), content))
+    }
+}
+
+impl<E, C> Item<E, C> for JsonEdit {
+    fn process<'a>(
+        self,
+        mut ctx: Context<'a, E, C>,
+        _env: &'a mut Spurgt<E>,
+    ) -> impl std::future::Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            let existing = ctx.require_existing(&self.path).await?;
+            let file = self.apply(Some(&existing.content))?;
+            ctx.file(File {
+                content: file.content,
+                ..existing
+            })?;
+            Ok(())
+        }
+    }
+}
+
+pub fn json(path: impl Into<RelativePathBuf>) -> JsonEdit {
+    JsonEdit::new(path)
+}
+
+fn set(document: &mut Value, pointer: &str, value: Value) -> Result<(), GeenieError> {
+    let tokens = tokens(pointer)?;
+    let Some((last, parents)) = tokens.split_last() else {
+        *document = value;
+        return Ok(());
+    };
+
+    let mut current = document;
+    for (position, token) in parents.iter().enumerate() {
+        let next = &tokens[position + 1];
+        current = slot(current, token, pointer, || {
+            if next == "-" {
+                Value::Array(Vec::new())
+            } else {
+                Value::Object(Map::new())
+            }
+        })?;
+    }
+
+    *slot(current, last, pointer, || Value::Null)? = value;
+    Ok(())
+}
+
+/// The child of a container at `token`, created with `missing` if absent.
+fn slot<'a>(
+    current: &'a mut Value,
+    token: &str,
+    pointer: &str,
+    missing: impl FnOnce() -> Value,
+) -> Result<&'a mut Value, GeenieError> {
+    match current {
+        Value::Object(map) => Ok(map.entry(token).or_insert_with(missing)),
+        Value::Array(list) => {
+            let index = match token {
+                "-" => list.len(),
+                token => index(token, list.len(), pointer)?,
+            };
+            if index == list.len() {
+                list.push(missing());
+            }
+            Ok(&mut list[index])
+        }
+        value => Err(GeenieError::backend(format!(
+            "cannot set {pointer}: {value} is not an object or array"
+        ))),
+    }
+}
+
+fn patch(document: &mut Value, operation: PatchOperation) -> Result<(), GeenieError> {
+    match operation {
+        PatchOperation::Add { path, value } => add(document, &path, value),
+        PatchOperation::Remove { path } => remove(document, &path).map(|_| ()),
+        PatchOperation::Replace { path, value } => {
+            *lookup(document, &path)? = value;
+            Ok(())
+        }
+        PatchOperation::Move { from, path } => {
+            if path.starts_with(&format!("{from}/")) {
+                return Err(GeenieError::backend(format!(
+                    "cannot move {from} into its own child {path}"
+                )));
+            }
+            let value = remove(document, &from)?;
+            add(document, &path, value)
+        }
+        PatchOperation::Copy { from, path } => {
+            let value = lookup(document, &from)?.clone();
+            add(document, &path, value)
+        }
+        PatchOperation::Test { path, value } => {
+            if *lookup(document, &path)? == value {
+                Ok(())
+            } else {
+                Err(GeenieError::backend(format!(
+                    "json patch test failed at {path}"
+                )))
+            }
+        }
+    }
+}
+
+fn add(document: &mut Value, pointer: &str, value: Value) -> Result<(), GeenieError> {
+    let mut tokens = tokens(pointer)?;
+    let Some(last) = tokens.pop() else {
+        *document = value;
+        return Ok(());
+    };
+
+    match walk(document, &tokens, pointer)? {
+        Value::Object(map) => {
+            map.insert(last, value);
+        }
+        Value::Array(list) if last == "-" => list.push(value),
+        Value::Array(list) => {
+            let index = index(&last, list.len(), pointer)?;
+            list.insert(index, value);
+        }
+        _ => return Err(not_found(pointer)),
+    }
+
+    Ok(())
+}
+
+fn remove(document: &mut Value, pointer: &str) -> Result<Value, GeenieError> {
+    let mut tokens = tokens(pointer)?;
+    let Some(last) = tokens.pop() else {
+        return Ok(std::mem::replace(document, Value::Null));
+    };
+
+    match walk(document, &tokens, pointer)? {
+        Value::Object(map) => map.shift_remove(&last).ok_or_else(|| not_found(pointer)),
+        Value::Array(list) => {
+            let index = index(&last, list.len(), pointer)?;
+            if index < list.len() {
+                Ok(list.remove(index))
+            } else {
+                Err(not_found(pointer))
+            }
+        }
+        _ => Err(not_found(pointer)),
+    }
+}
+
+fn lookup<'a>(document: &'a mut Value, pointer: &str) -> Result<&'a mut Value, GeenieError> {
+    walk(document, &tokens(pointer)?, pointer)
+}
+
+fn walk<'a>(
+    document: &'a mut Value,
+    tokens: &[String],
+    pointer: &str,
+) -> Result<&'a mut Value, GeenieError> {
+    let mut current = document;
+    for token in tokens {
+        current = match current {
+            Value::Object(map) => map.get_mut(token),
+            Value::Array(list) => token
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| list.get_mut(index)),
+            _ => None,
+        }
+        .ok_or_else(|| not_found(pointer))?;
+    }
+    Ok(current)
+}
+
+fn tokens(pointer: &str) -> Result<Vec<String>, GeenieError> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let Some(rest) = pointer.strip_prefix('/') else {
+        return Err(GeenieError::backend(format!(
+            "invalid json pointer: {pointer}"
+        )));
+    };
+
+    Ok(rest
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+fn index(token: &str, len: usize, pointer: &str) -> Result<usize, GeenieError> {
+    match token.parse::<usize>() {
+        Ok(index) if index <= len && (token == "0" || !token.starts_with('0')) => Ok(index),
+        _ => Err(not_found(pointer)),
+    }
+}
+
+fn not_found(pointer: &str) -> GeenieError {
+    GeenieError::backend(format!("json pointer not found: {pointer}"))
+}
+
+fn detect_indent(source: &str) -> String {
+    source
+        .lines()
+        .skip(1)
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .find(|indent| !indent.is_empty())
+        .unwrap_or("  ")
+        .to_string()
+}
+
+fn to_edit(value: impl Serialize, edit: impl FnOnce(Value) -> Edit) -> Edit {
+    match serde_json::to_value(value) {
+        Ok(value) => edit(value),
+        Err(err) => Edit::Invalid(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json as value;
+
+    use super::*;
+
+    fn edit(source: &str, edit: JsonEdit) -> Result<String, GeenieError> {
+        let file = edit.apply(Some(source.as_bytes()))?;
+        Ok(String::from_utf8(file.content).unwrap())
+    }
+
+    #[test]
+    fn sets_values_by_pointer() {
+        let output = edit(
+            "{\"b\": 1, \"a\": [1]}\n",
+            json("package.json")
+                .set("/a/-", 2)
+                .set("/a/0", 0)
+                .set("/c/d~1e/f~0g", true)
+                .set("/b", value!({ "x": 1 })),
+        )
+        .unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<Value>(&output).unwrap(),
+            value!({ "b": { "x": 1 }, "a": [0, 2], "c": { "d/e": { "f~g": true } } })
+        );
+        assert!(edit("{}", json("a.json").set("/a/01", 1)).is_ok());
+        assert!(edit("{\"a\": []}", json("a.json").set("/a/01", 1)).is_err());
+        assert!(edit("{\"a\": []}", json("a.json").set("/a/2", 1)).is_err());
+        assert!(edit("{}", json("a.json").set("a", 1)).is_err());
+    }
+
+    #[test]
+    fn refuses_to_replace_values_on_the_path() {
+        let source = r#"{"name": "app", "list": [1], "none": null}"#;
+        for pointer in ["/name/first", "/list/0/a", "/none/a"] {
+            let err = edit(source, json("a.json").set(pointer, 1)).unwrap_err();
+            assert!(
+                err.to_string().contains("is not an object or array"),
+                "{err}"
+            );
+        }
+
+        assert_eq!(
+            serde_json::from_str::<Value>(
+                &edit(source, json("a.json").set("/list/-/a", 1).set("/b/-", 1)).unwrap()
+            )
+            .unwrap(),
+            value!({ "name": "app", "list": [1, { "a": 1 }], "none": null, "b": [1] })
+        );
+    }
+
+    #[test]
+    fn reports_values_json_cannot_represent() {
+        let keys = std::collections::BTreeMap::from([((1, 2), "a")]);
+
+        for edit in [
+            json("a.json").merge(&keys),
+            json("a.json").set("/a", &keys),
+            json("a.json").patch(&keys),
+        ] {
+            let err = edit.apply(Some(b"{}")).unwrap_err();
+            assert!(
+                err.to_string()
+                    .starts_with("backend: invalid json value for a.json"),
+                "{err}"
+            );
+        }
+    }
+
+    #[test]
+    fn applies_patches() {
+        let output = edit(
+            r#"{"a": 1, "b": [1, 2], "c": {"d": 1}}"#,
+            json("a.json").patch(value!([
+                { "op": "add", "path": "/b/1", "value": 9 },
+                { "op": "remove", "path": "/a" },
+                { "op": "replace", "path": "/c/d", "value": 2 },
+                { "op": "copy", "from": "/c", "path": "/e" },
+                { "op": "move", "from": "/b", "path": "/c/b" },
+                { "op": "test", "path": "/e/d", "value": 2 },
+            ])),
+        )
+        .unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<Value>(&output).unwrap(),
+            value!({ "c": { "d": 2, "b": [1, 9, 2] }, "e": { "d": 2 } })
+        );
+
+        let failing = [
+            value!({ "op": "test", "path": "/a", "value": 2 }),
+            value!({ "op": "remove", "path": "/missing" }),
+            value!({ "op": "move", "from": "/c", "path": "/c/d" }),
+            value!({ "op": "unknown", "path": "/a" }),
+        ];
+        for operation in failing {
+            assert!(edit(r#"{"a": 1, "c": {}}"#, json("a.json").patch(operation)).is_err());
+        }
+    }
+
+    #[test]
+    fn keeps_formatting_and_key_order() {
+        let source = "{\n    \"name\": \"app\",\n    \"dependencies\": {}\n}\n";
+        let output = edit(
+            source,
+            json("package.json").merge(value!({ "dependencies": { "zod": "^3" }, "a": 1 })),
+        )
+        .unwrap();
+
+        assert_eq!(
+            output,
+            "{\n    \"name\": \"app\",\n    \"dependencies\": {\n        \"zod\": \"^3\"\n    },\n    \"a\": 1\n}\n"
+        );
+        assert_eq!(
+            edit("{\"a\":1}", json("a.json")).unwrap(),
+            "{\n  \"a\": 1\n}"
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn edits_generated_output_and_requires_an_existing_file() {
+        use futures::executor::block_on;
+
+        use crate::{item::MountItem, testing::TestEnv, ItemExt};
+
+        let env = TestEnv::new();
+        let target = block_on(env.render(
+            File::new("app/package.json", "{}\n").then(MountItem::new(
+                "app",
+                json("package.json").set("/name", "app"),
+            )),
+            &mut (),
+        ))
+        .unwrap();
+
+        assert_eq!(
+            target.get("app/package.json").unwrap(),
+            b"{\n  \"name\": \"app\"\n}\n"
+        );
+
+        let file = json("a.json").apply(Some(b"{}")).unwrap();
+        assert_eq!(file.conflict, None);
+
+        let err = block_on(env.run_item(json("package.json").set("/name", "app"), &mut ()))
+            .map(drop)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "cannot edit missing file: package.json");
+    }
+}
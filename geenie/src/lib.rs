@@ -1,12 +1,40 @@
+mod answers;
+mod combinator;
 mod command;
+mod conflict;
 mod context;
+pub mod diff;
 mod error;
 mod file;
 mod geenie;
+#[cfg(feature = "inject")]
+mod inject;
 mod item;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "manifest")]
+pub mod manifest;
+mod merge;
+mod plan;
 #[cfg(feature = "process")]
 mod process;
 mod result;
+#[cfg(feature = "testing")]
+mod snapshot;
+mod target;
+#[cfg(feature = "template")]
+mod template;
+#[cfg(all(feature = "fs", feature = "template"))]
+mod template_dir;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "toml_edit")]
+mod toml_doc;
+#[cfg(feature = "process")]
+mod tools;
+mod transaction;
+#[cfg(feature = "update")]
+mod update;
 
 pub mod questions {
     pub use spurgt::{
@@ -23,15 +51,55 @@ pub mod ui {
 pub use spurgt_cliclack::Cliclack as Cli;
 
 pub use self::{
-    command::{Command, DynamicCommand},
+    answers::{Answers, AnswersEnv, Session},
+    combinator::{FilterFiles, MapFiles, Optional, OrElse, Then, When},
+    command::{Command, CommandReport, CommandRun, CommandStatus, DynamicCommand, Step},
+    conflict::{ConflictPolicy, WriteOutcome, WriteReport, WrittenFile},
     context::Context,
-    error::GeenieError,
-    file::{File, FileList},
+    error::{ExitReason, GeenieError, MissingTool, ProcessFailure, ToolProblem},
+    file::{File, FileKind, FileList},
     geenie::Geenie,
     item::{Item, ItemExt, MountItem},
+    merge::MergeStrategy,
+    plan::{FileAction, Plan, PlannedFile},
+    target::{MemoryEntry, MemoryTarget, Target},
+    transaction::Transaction,
 };
 
+#[cfg(feature = "fs")]
+pub use self::target::FsTarget;
+
+#[cfg(feature = "tar")]
+pub use self::target::TarTarget;
+
+#[cfg(feature = "zip")]
+pub use self::target::ZipTarget;
+
+#[cfg(feature = "inject")]
+pub use self::inject::{inject, Inject};
+
+#[cfg(feature = "json")]
+pub use self::json::{json, JsonEdit, PatchOperation};
+
 #[cfg(feature = "process")]
 pub use self::process::*;
 
+#[cfg(feature = "process")]
+pub use self::tools::{prerequisites, which, Prerequisites, Tool, Version};
+
+#[cfg(feature = "template")]
+pub use self::template::{render, template, Template};
+
+#[cfg(all(feature = "fs", feature = "template"))]
+pub use self::template_dir::{template_dir, TemplateDir};
+
+#[cfg(feature = "toml_edit")]
+pub use self::toml_doc::{toml, Dependency, TomlEdit};
+
+#[cfg(feature = "manifest")]
+pub use self::manifest::{Manifest, ManifestData};
+
+#[cfg(feature = "update")]
+pub use self::update::{Metadata, Update};
+
 pub use relative_path;
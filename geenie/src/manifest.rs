@@ -0,0 +1,480 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::BuildHasher,
+    path::{Path, PathBuf},
+};
+
+use minijinja::Value;
+use relative_path::RelativePathBuf;
+use serde::Deserialize;
+use spurgt::{Asger, Spurgt};
+
+use crate::{
+    process,
+    questions::{confirm, input, multi_select, password, select},
+    template::{condition, render},
+    Context, File, Geenie, GeenieError, Item, TemplateDir,
+};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Manifest {
+    pub name: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub questions: Vec<Question>,
+    #[serde(default)]
+    pub files: Vec<FileEntry>,
+    #[serde(default)]
+    pub templates: Vec<TemplateEntry>,
+    #[serde(default)]
+    pub commands: Vec<CommandEntry>,
+    #[serde(skip)]
+    base: PathBuf,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Question {
+    pub id: String,
+    pub prompt: String,
+    #[serde(flatten)]
+    pub kind: QuestionKind,
+    #[serde(default)]
+    pub when: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QuestionKind {
+    Input {
+        #[serde(default)]
+        default: Option<String>,
+    },
+    Password,
+    Confirm,
+    Select {
+        options: Vec<Choice>,
+    },
+    MultiSelect {
+        options: Vec<Choice>,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Choice {
+    Value(String),
+    Detailed {
+        value: String,
+        #[serde(default)]
+        label: Option<String>,
+        #[serde(default)]
+        hint: Option<String>,
+    },
+}
+
+impl Choice {
+    fn parts(&self) -> (String, String, String) {
+        match self {
+            Choice::Value(value) => (value.clone(), value.clone(), String::new()),
+            Choice::Detailed { value, label, hint } => (
+                value.clone(),
+                label.clone().unwrap_or_else(|| value.clone()),
+                hint.clone().unwrap_or_default(),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FileEntry {
+    pub path: String,
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub when: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TemplateEntry {
+    pub source: String,
+    #[serde(default)]
+    pub mount: Option<String>,
+    #[serde(default)]
+    pub when: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommandEntry {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub output: bool,
+    #[serde(default)]
+    pub when: Option<String>,
+}
+
+impl Manifest {
+    pub fn from_toml(source: &str) -> Result<Manifest, GeenieError> {
+        toml::from_str(source).map_err(GeenieError::backend)
+    }
+
+    pub fn from_yaml(source: &str) -> Result<Manifest, GeenieError> {
+        serde_yaml::from_str(source).map_err(GeenieError::backend)
+    }
+
+    pub async fn load(path: impl AsRef<Path>) -> Result<Manifest, GeenieError> {
+        let path = path.as_ref();
+        let source = async_fs::read_to_string(path).await?;
+
+        let manifest = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => Manifest::from_yaml(&source)?,
+            _ => Manifest::from_toml(&source)?,
+        };
+
+        let base = path.parent().unwrap_or(Path::new("."));
+        Ok(manifest.base(base))
+    }
+
+    pub fn base(mut self, base: impl Into<PathBuf>) -> Self {
+        self.base = base.into();
+        self
+    }
+
+    pub fn into_geenie<E, C>(self, env: E) -> Geenie<E, C>
+    where
+        E: spurgt::core::Env + Asger + 'static,
+        C: ManifestData + 'static,
+    {
+        let mut geenie = Geenie::new(env);
+        geenie.push(self);
+        geenie
+    }
+}
+
+pub trait ManifestData {
+    fn answer(&mut self, id: &str, value: serde_json::Value);
+}
+
+impl ManifestData for () {
+    fn answer(&mut self, _id: &str, _value: serde_json::Value) {}
+}
+
+impl ManifestData for serde_json::Map<String, serde_json::Value> {
+    fn answer(&mut self, id: &str, value: serde_json::Value) {
+        self.insert(id.to_string(), value);
+    }
+}
+
+impl ManifestData for serde_json::Value {
+    fn answer(&mut self, id: &str, value: serde_json::Value) {
+        if !self.is_object() {
+            *self = serde_json::Value::Object(Default::default());
+        }
+        if let serde_json::Value::Object(map) = self {
+            map.answer(id, value);
+        }
+    }
+}
+
+impl ManifestData for BTreeMap<String, serde_json::Value> {
+    fn answer(&mut self, id: &str, value: serde_json::Value) {
+        self.insert(id.to_string(), value);
+    }
+}
+
+impl<S: BuildHasher> ManifestData for HashMap<String, serde_json::Value, S> {
+    fn answer(&mut self, id: &str, value: serde_json::Value) {
+        self.insert(id.to_string(), value);
+    }
+}
+
+impl Question {
+    async fn ask<E: Asger, C>(
+        &self,
+        ctx: &mut Context<'_, E, C>,
+        env: &mut Spurgt<E>,
+    ) -> Result<serde_json::Value, GeenieError> {
+        let id = self.id.as_str();
+        let prompt = self.prompt.as_str();
+
+        let value = match &self.kind {
+            QuestionKind::Input { default: None } => ctx
+                .ask::<String, _, _>(id, env, async |env| env.ask(input(prompt)).await)
+                .await?
+                .into(),
+            QuestionKind::Input {
+                default: Some(default),
+            } => ctx
+                .ask_or(id, default.clone(), env, async |env| {
+                    env.ask(input(prompt).default(default)).await
+                })
+                .await?
+                .into(),
+            QuestionKind::Password => ctx
+                .ask::<String, _, _>(id, env, async |env| env.ask(password(prompt)).await)
+                .await?
+                .into(),
+            QuestionKind::Confirm => ctx
+                .ask::<bool, _, _>(id, env, async |env| env.ask(confirm(prompt)).await)
+                .await?
+                .into(),
+            QuestionKind::Select { options } => {
+                let mut question = select(prompt);
+                for option in options {
+                    let (value, label, hint) = option.parts();
+                    question = question.item(value, label, hint);
+                }
+                let answer = ctx
+                    .ask::<String, _, _>(id, env, async |env| env.ask(question).await)
+                    .await?;
+                option_value(id, options, &answer)?.into()
+            }
+            QuestionKind::MultiSelect { options } => {
+                let mut question = multi_select(prompt);
+                for option in options {
+                    let (value, label, hint) = option.parts();
+                    question = question.item(value, label, hint);
+                }
+                ctx.ask::<Vec<String>, _, _>(id, env, async |env| env.ask(question).await)
+                    .await?
+                    .iter()
+                    .map(|answer| option_value(id, options, answer))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into()
+            }
+        };
+
+        Ok(value)
+    }
+}
+
+/// The value of the option an answer picks, by value or by label.
+fn option_value(id: &str, options: &[Choice], answer: &str) -> Result<String, GeenieError> {
+    options
+        .iter()
+        .map(Choice::parts)
+        .find(|(value, label, _)| value == answer || label == answer)
+        .map(|(value, _, _)| value)
+        .ok_or_else(|| {
+            GeenieError::invalid_answer(id, format!("{answer} is not one of the options"))
+        })
+}
+
+fn applies(when: &Option<String>, data: &Value) -> Result<bool, GeenieError> {
+    match when {
+        Some(expr) => condition(expr, data),
+        None => Ok(true),
+    }
+}
+
+impl<E: Asger + 'static, C: ManifestData> Item<E, C> for Manifest {
+    fn process<'a>(
+        self,
+        mut ctx: Context<'a, E, C>,
+        env: &'a mut Spurgt<E>,
+    ) -> impl std::future::Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            let mut answers = BTreeMap::<String, serde_json::Value>::new();
+
+            for question in &self.questions {
+                if !applies(&question.when, &Value::from_serialize(&answers))? {
+                    continue;
+                }
+                let value = question.ask(&mut ctx, env).await?;
+                ctx.data_mut().answer(&question.id, value.clone());
+                answers.insert(question.id.clone(), value);
+            }
+
+            let data = Value::from_serialize(&answers);
+
+            for file in self.files {
+                if !applies(&file.when, &data)? {
+                    continue;
+                }
+
+                let path = render(&file.path, &file.path, &data)?;
+                let source = match (file.content, file.source) {
+                    (Some(content), _) => content,
+                    (None, Some(source)) => {
+                        async_fs::read_to_string(self.base.join(source)).await?
+                    }
+                    (None, None) => String::new(),
+                };
+
+                ctx.file(File::new(path.as_str(), render(&path, &source, &data)?))?;
+            }
+
+            for template in self.templates {
+                if !applies(&template.when, &data)? {
+                    continue;
+                }
+
+                let mount = match &template.mount {
+                    Some(mount) => RelativePathBuf::from(render(mount, mount, &data)?),
+                    None => RelativePathBuf::new(),
+                };
+
+                let files = TemplateDir::new(self.base.join(&template.source))
+                    .render(&data)
+                    .await?;
+
+                for file in files {
+                    ctx.file(File {
+                        path: mount.join(&file.path),
+                        ..file
+                    })?;
+                }
+            }
+
+            for command in self.commands {
+                if !applies(&command.when, &data)? {
+                    continue;
+                }
+
+                let mut cmd = process(render(&command.program, &command.program, &data)?);
+                for arg in &command.args {
+                    cmd = cmd.arg(render(arg, arg, &data)?);
+                }
+                ctx.command(cmd.output(command.output));
+            }
+
+            Ok(())
+        }
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::testing::TestEnv;
+
+    const MANIFEST: &str = r##"
+name = "demo"
+
+[[questions]]
+id = "name"
+prompt = "Project name"
+type = "input"
+
+[[questions]]
+id = "license"
+prompt = "Add a license?"
+type = "confirm"
+
+[[files]]
+path = "{{ name }}/README.md"
+content = "# {{ name }}\n"
+
+[[files]]
+path = "LICENSE"
+content = "MIT"
+when = "license"
+"##;
+
+    #[test]
+    fn parses_toml_and_yaml() {
+        let toml = Manifest::from_toml(MANIFEST).unwrap();
+        let yaml = Manifest::from_yaml(
+            "name: demo\nquestions:\n  - id: kind\n    prompt: Kind\n    type: select\n    options: [lib, { value: bin, label: Binary }]\n",
+        )
+        .unwrap();
+
+        assert_eq!(toml.questions.len(), 2);
+        assert!(matches!(toml.questions[1].kind, QuestionKind::Confirm));
+        assert!(matches!(
+            &yaml.questions[0].kind,
+            QuestionKind::Select { options } if options.len() == 2
+        ));
+    }
+
+    #[test]
+    fn renders_files_and_skips_conditions() {
+        let env = TestEnv::new()
+            .answer("name", "app")
+            .answer("license", false);
+        let mut data = BTreeMap::new();
+
+        let target =
+            block_on(env.render(Manifest::from_toml(MANIFEST).unwrap(), &mut data)).unwrap();
+
+        assert_eq!(
+            target.files().into_keys().collect::<Vec<_>>(),
+            vec![RelativePathBuf::from("app/README.md")]
+        );
+        assert_eq!(target.get("app/README.md").unwrap(), b"# app\n");
+    }
+
+    #[test]
+    fn exposes_answers_to_context() {
+        let env = TestEnv::new().answer("name", "app").answer("license", true);
+        let mut data = serde_json::Value::Null;
+
+        block_on(env.run_item(Manifest::from_toml(MANIFEST).unwrap(), &mut data)).unwrap();
+
+        assert_eq!(data, serde_json::json!({ "name": "app", "license": true }));
+    }
+
+    #[test]
+    fn coerces_overrides_by_question_kind() {
+        let mut answers = crate::Answers::new();
+        answers.overrides(["name=42", "license=true"]).unwrap();
+        let mut data = serde_json::Value::Null;
+
+        block_on(
+            TestEnv::new()
+                .answers(answers)
+                .run_item(Manifest::from_toml(MANIFEST).unwrap(), &mut data),
+        )
+        .unwrap();
+
+        assert_eq!(data, serde_json::json!({ "name": "42", "license": true }));
+    }
+
+    const CHOICES: &str = "name: demo\nquestions:\n  - id: name\n    prompt: Name\n    type: input\n    default: demo\n  - id: kind\n    prompt: Kind\n    type: select\n    options: [lib, { value: bin, label: Binary }]\n";
+
+    fn answered(env: TestEnv) -> Result<serde_json::Value, GeenieError> {
+        let mut data = serde_json::Value::Null;
+        block_on(env.run_item(Manifest::from_yaml(CHOICES).unwrap(), &mut data)).map(drop)?;
+        Ok(data)
+    }
+
+    #[test]
+    fn strict_runs_take_input_defaults() {
+        let mut answers = crate::Answers::new();
+        answers.set("kind", "Binary").unwrap();
+
+        assert_eq!(
+            answered(TestEnv::new().answers(answers.strict(true))).unwrap(),
+            serde_json::json!({ "name": "demo", "kind": "bin" })
+        );
+    }
+
+    #[test]
+    fn prompts_show_the_default() {
+        let env = TestEnv::new()
+            .expect_input("Name", "")
+            .expect_select("Kind", 0);
+
+        assert_eq!(
+            answered(env).unwrap(),
+            serde_json::json!({ "name": "demo", "kind": "lib" })
+        );
+    }
+
+    #[test]
+    fn rejects_answers_outside_the_options() {
+        let err =
+            answered(TestEnv::new().answer("name", "app").answer("kind", "other")).unwrap_err();
+
+        assert!(
+            matches!(&err, GeenieError::InvalidAnswer { id, .. } if id == "kind"),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::BTreeSet;
+
+use relative_path::RelativePath;
+#[cfg(feature = "json")]
+use serde_json::Value;
+
+use crate::GeenieError;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergeStrategy {
+    #[default]
+    Error,
+    Append,
+    LineUnion,
+    #[cfg(feature = "json")]
+    Json,
+    #[cfg(feature = "toml_edit")]
+    Toml,
+    LastWins,
+    FirstWins,
+}
+
+impl MergeStrategy {
+    pub fn merge(
+        &self,
+        path: &RelativePath,
+        existing: &[u8],
+        incoming: &[u8],
+    ) -> Result<Vec<u8>, GeenieError> {
+        match self {
+            MergeStrategy::Error => Err(GeenieError::duplicate(path.to_relative_path_buf())),
+            MergeStrategy::Append => {
+                let mut content = existing.to_vec();
+                if !content.is_empty() && !content.ends_with(b"\n") {
+                    content.push(b'\n');
+                }
+                content.extend_from_slice(incoming);
+                Ok(content)
+            }
+            MergeStrategy::LineUnion => Ok(line_union(existing, incoming)),
+            #[cfg(feature = "json")]
+            MergeStrategy::Json => {
+                let mut document = parse_json(path, existing)?;
+                merge_json(&mut document, parse_json(path, incoming)?);
+                let mut content =
+                    serde_json::to_vec_pretty(&document).map_err(GeenieError::backend)?;
+                content.push(b'\n');
+                Ok(content)
+            }
+            #[cfg(feature = "toml_edit")]
+            MergeStrategy::Toml => crate::toml_doc::merge_documents(path, existing, incoming),
+            MergeStrategy::LastWins => Ok(incoming.to_vec()),
+            MergeStrategy::FirstWins => Ok(existing.to_vec()),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+pub(crate) fn merge_json(target: &mut Value, value: Value) {
+    match (target, value) {
+        (Value::Object(target), Value::Object(value)) => {
+            for (key, value) in value {
+                match target.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, value) => *target = value,
+    }
+}
+
+#[cfg(feature = "json")]
+fn parse_json(path: &RelativePath, content: &[u8]) -> Result<Value, GeenieError> {
+    if content.iter().all(u8::is_ascii_whitespace) {
+        return Ok(Value::Object(Default::default()));
+    }
+
+    serde_json::from_slice(content)
+        .map_err(|err| GeenieError::backend(format!("could not parse {path}: {err}")))
+}
+
+fn line_union(existing: &[u8], incoming: &[u8]) -> Vec<u8> {
+    let existing = String::from_utf8_lossy(existing);
+    let incoming = String::from_utf8_lossy(incoming);
+
+    let mut seen = existing.lines().map(str::trim_end).collect::<BTreeSet<_>>();
+    let mut content = existing.to_string();
+
+    for line in incoming.lines() {
+        if !seen.insert(line.trim_end()) {
+            continue;
+        }
+
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(line);
+        content.push('\n');
+    }
+
+    content.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merge(strategy: MergeStrategy, existing: &str, incoming: &str) -> String {
+        let content = strategy
+            .merge(
+                RelativePath::new("file"),
+                existing.as_bytes(),
+                incoming.as_bytes(),
+            )
+            .unwrap();
+        String::from_utf8(content).unwrap()
+    }
+
+    #[test]
+    fn merges_text() {
+        assert_eq!(merge(MergeStrategy::Append, "a", "b\n"), "a\nb\n");
+        assert_eq!(merge(MergeStrategy::Append, "", "b\n"), "b\n");
+        assert_eq!(
+            merge(
+                MergeStrategy::LineUnion,
+                "target\n.env",
+                ".env \nnode_modules\n"
+            ),
+            "target\n.env\nnode_modules\n"
+        );
+        assert_eq!(merge(MergeStrategy::LastWins, "a", "b"), "b");
+        assert_eq!(merge(MergeStrategy::FirstWins, "a", "b"), "a");
+        assert!(matches!(
+            MergeStrategy::Error.merge(RelativePath::new("file"), b"a", b"b"),
+            Err(GeenieError::Duplicate { .. })
+        ));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn merges_json_in_document_order() {
+        assert_eq!(
+            merge(
+                MergeStrategy::Json,
+                r#"{"name": "app", "scripts": {"build": "vite"}, "dependencies": {}}"#,
+                r#"{"scripts": {"test": "vitest", "build": "tsc"}, "author": "me"}"#,
+            ),
+            "{\n  \"name\": \"app\",\n  \"scripts\": {\n    \"build\": \"tsc\",\n    \"test\": \"vitest\"\n  },\n  \"dependencies\": {},\n  \"author\": \"me\"\n}\n"
+        );
+        assert_eq!(merge(MergeStrategy::Json, " \n", "[1]"), "[\n  1\n]\n");
+        assert!(MergeStrategy::Json
+            .merge(RelativePath::new("file"), b"{", b"{}")
+            .is_err());
+    }
+
+    #[cfg(feature = "toml_edit")]
+    #[test]
+    fn merges_toml() {
+        assert_eq!(
+            merge(
+                MergeStrategy::Toml,
+                "[package]\nname = \"app\"\n",
+                "[package]\nedition = \"2021\"\n"
+            ),
+            "[package]\nname = \"app\"\nedition = \"2021\"\n"
+        );
+    }
+}
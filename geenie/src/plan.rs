@@ -0,0 +1,66 @@
+use core::fmt;
+
+use relative_path::RelativePathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileAction {
+    Create,
+    Overwrite,
+    Unchanged,
+    Skip,
+    Sidecar,
+    Merge,
+    Ask,
+    Conflict,
+}
+
+impl fmt::Display for FileAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileAction::Create => f.write_str("create"),
+            FileAction::Overwrite => f.write_str("overwrite"),
+            FileAction::Unchanged => f.write_str("unchanged"),
+            FileAction::Skip => f.write_str("skip"),
+            FileAction::Sidecar => f.write_str("sidecar"),
+            FileAction::Merge => f.write_str("merge"),
+            FileAction::Ask => f.write_str("ask"),
+            FileAction::Conflict => f.write_str("conflict"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedFile {
+    pub path: RelativePathBuf,
+    pub action: FileAction,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Plan {
+    pub files: Vec<PlannedFile>,
+    pub commands: Vec<String>,
+}
+
+impl Plan {
+    pub fn conflicts(&self) -> impl Iterator<Item = &PlannedFile> {
+        self.files
+            .iter()
+            .filter(|file| file.action == FileAction::Conflict)
+    }
+
+    pub fn has_conflicts(&self) -> bool {
+        self.conflicts().next().is_some()
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for file in &self.files {
+            writeln!(f, "{:>9} {}", file.action, file.path)?;
+        }
+        for cmd in &self.commands {
+            writeln!(f, "{:>9} {}", "run", cmd)?;
+        }
+        Ok(())
+    }
+}
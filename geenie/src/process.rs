@@ -1,14 +1,106 @@
-use std::path::Path;
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    time::{Duration, Instant},
+};
 
-use async_process::Command;
+use async_process::{Child, Command, Stdio};
+use futures::{
+    channel::mpsc::{self, UnboundedSender},
+    future::Either,
+    io::BufReader,
+    AsyncBufReadExt, AsyncRead, AsyncWriteExt, StreamExt,
+};
+use relative_path::RelativePathBuf;
 
-use crate::{GeenieError, Item};
+use crate::{
+    error::{ExitReason, ProcessFailure},
+    GeenieError, Item,
+};
 use spurgt::{Asger, Spinner, Spurgt};
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum EnvChange {
+    Set(String, String),
+    Remove(String),
+    Clear,
+}
+
 pub struct Process {
     cmd: String,
     args: Vec<String>,
     output: bool,
+    name: Option<String>,
+    after: Vec<String>,
+    concurrent: bool,
+    envs: Vec<EnvChange>,
+    stdin: Option<Vec<u8>>,
+    current_dir: Option<RelativePathBuf>,
+    timeout: Option<Duration>,
+    shell: bool,
+    exit_codes: Vec<i32>,
+    tail: usize,
+    optional: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Captured {
+    pub status: ExitStatus,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl Captured {
+    pub fn text(&self) -> &str {
+        self.stdout.trim()
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.stdout.lines()
+    }
+
+    pub fn parse<T>(&self) -> Result<T, GeenieError>
+    where
+        T: core::str::FromStr,
+        T::Err: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        self.text().parse().map_err(GeenieError::backend)
+    }
+
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T, GeenieError> {
+        serde_json::from_str(&self.stdout).map_err(GeenieError::backend)
+    }
+}
+
+struct Tail {
+    lines: RefCell<VecDeque<String>>,
+    limit: usize,
+}
+
+impl Tail {
+    fn new(limit: usize) -> Tail {
+        Tail {
+            lines: RefCell::new(VecDeque::with_capacity(limit)),
+            limit,
+        }
+    }
+
+    fn push(&self, line: String) {
+        let mut lines = self.lines.borrow_mut();
+        if lines.len() == self.limit {
+            lines.pop_front();
+        }
+        if self.limit > 0 {
+            lines.push_back(line);
+        }
+    }
+
+    fn into_lines(self) -> Vec<String> {
+        self.lines.into_inner().into()
+    }
 }
 
 impl Process {
@@ -21,6 +113,340 @@ impl Process {
         self.output = output;
         self
     }
+
+    pub fn named(mut self, name: impl ToString) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn after(mut self, name: impl ToString) -> Self {
+        self.after.push(name.to_string());
+        self
+    }
+
+    pub fn concurrent(mut self) -> Self {
+        self.concurrent = true;
+        self
+    }
+
+    pub fn env(mut self, key: impl ToString, value: impl ToString) -> Self {
+        self.envs
+            .push(EnvChange::Set(key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn env_remove(mut self, key: impl ToString) -> Self {
+        self.envs.push(EnvChange::Remove(key.to_string()));
+        self
+    }
+
+    pub fn env_clear(mut self) -> Self {
+        self.envs.push(EnvChange::Clear);
+        self
+    }
+
+    pub fn stdin(mut self, input: impl Into<Vec<u8>>) -> Self {
+        self.stdin = Some(input.into());
+        self
+    }
+
+    pub fn current_dir(mut self, dir: impl Into<RelativePathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn shell(mut self, shell: bool) -> Self {
+        self.shell = shell;
+        self
+    }
+
+    pub fn exit_codes(mut self, codes: impl IntoIterator<Item = i32>) -> Self {
+        self.exit_codes = codes.into_iter().collect();
+        self
+    }
+
+    pub fn tail(mut self, lines: usize) -> Self {
+        self.tail = lines;
+        self
+    }
+
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+
+    pub fn is_available(&self) -> bool {
+        crate::tools::which(self.program()).is_some()
+    }
+
+    /// The program that is run, which in shell mode is the first word of the
+    /// command.
+    fn program(&self) -> &str {
+        if self.shell {
+            self.cmd.split_whitespace().next().unwrap_or_default()
+        } else {
+            self.cmd.as_str()
+        }
+    }
+
+    pub async fn capture(&self) -> Result<Captured, GeenieError> {
+        self.capture_in(&std::env::current_dir()?).await
+    }
+
+    pub async fn capture_in(&self, path: &Path) -> Result<Captured, GeenieError> {
+        self.execute(path, None, true).await
+    }
+
+    pub fn with_output<F, I>(self, then: F) -> CaptureItem<F>
+    where
+        F: FnOnce(Captured) -> Result<I, GeenieError>,
+    {
+        CaptureItem {
+            process: self,
+            then,
+        }
+    }
+
+    fn command_line(&self) -> String {
+        core::iter::once(Cow::Borrowed(self.cmd.as_str()))
+            .chain(self.args.iter().map(|arg| quote(arg)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn invocation(&self) -> (String, Vec<String>) {
+        if !self.shell {
+            return (self.cmd.clone(), self.args.clone());
+        }
+
+        let line = self.command_line();
+        if cfg!(windows) {
+            (String::from("cmd"), vec![String::from("/C"), line])
+        } else {
+            (String::from("sh"), vec![String::from("-c"), line])
+        }
+    }
+
+    fn cwd(&self, path: &Path) -> PathBuf {
+        match &self.current_dir {
+            Some(dir) => dir.to_logical_path(path),
+            None => path.to_path_buf(),
+        }
+    }
+
+    fn command(&self, program: &str, args: &[String], cwd: &Path) -> Command {
+        let mut command = Command::new(program);
+        command.args(args);
+
+        for change in &self.envs {
+            match change {
+                EnvChange::Set(key, value) => command.env(key, value),
+                EnvChange::Remove(key) => command.env_remove(key),
+                EnvChange::Clear => command.env_clear(),
+            };
+        }
+
+        command
+            .current_dir(cwd)
+            .stdin(if self.stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        command
+    }
+
+    async fn execute(
+        &self,
+        path: &Path,
+        lines: Option<UnboundedSender<String>>,
+        capture: bool,
+    ) -> Result<Captured, GeenieError> {
+        let (program, args) = self.invocation();
+        let cwd = self.cwd(path);
+        let started = Instant::now();
+
+        let mut child = match self.command(&program, &args, &cwd).spawn() {
+            Ok(child) => child,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound && cwd.is_dir() => {
+                let suggestion = crate::tools::suggest(&program);
+                return Err(GeenieError::program_not_found(program, suggestion));
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let stdout = Tail::new(self.tail);
+        let stderr = Tail::new(self.tail);
+
+        let waited = match self.timeout {
+            Some(timeout) => {
+                let wait = Box::pin(self.wait(&mut child, (&stdout, &stderr), lines, capture));
+                match futures::future::select(wait, async_io::Timer::after(timeout)).await {
+                    Either::Left((captured, _)) => captured.map(Some),
+                    Either::Right(_) => Ok(None),
+                }
+            }
+            None => self
+                .wait(&mut child, (&stdout, &stderr), lines, capture)
+                .await
+                .map(Some),
+        };
+
+        let reason = match waited {
+            Ok(Some(captured)) => {
+                let code = captured.status.code();
+
+                if code.is_some_and(|code| self.exit_codes.contains(&code)) {
+                    return Ok(captured);
+                }
+
+                if self.shell && code == Some(SHELL_NOT_FOUND) {
+                    let program = self.program();
+                    let suggestion = crate::tools::suggest(program);
+                    return Err(GeenieError::program_not_found(program, suggestion));
+                }
+
+                exit_reason(captured.status)
+            }
+            Ok(None) => {
+                stop(&mut child).await?;
+                ExitReason::TimedOut(self.timeout.unwrap_or_default())
+            }
+            Err(err) => {
+                // The original error matters more than a failure to stop.
+                stop(&mut child).await.ok();
+                return Err(err);
+            }
+        };
+
+        Err(GeenieError::process(ProcessFailure {
+            program,
+            args,
+            cwd,
+            reason,
+            stdout: stdout.into_lines(),
+            stderr: stderr.into_lines(),
+            duration: started.elapsed(),
+        }))
+    }
+
+    async fn wait(
+        &self,
+        child: &mut Child,
+        (stdout_tail, stderr_tail): (&Tail, &Tail),
+        lines: Option<UnboundedSender<String>>,
+        capture: bool,
+    ) -> Result<Captured, GeenieError> {
+        let stdin = child.stdin.take();
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+
+        let write = async {
+            if let (Some(mut stdin), Some(input)) = (stdin, &self.stdin) {
+                stdin.write_all(input).await?;
+                stdin.close().await?;
+            }
+            Ok::<_, std::io::Error>(())
+        };
+
+        let (write, stdout, stderr, status) = futures::join!(
+            write,
+            read_lines(stdout, stdout_tail, lines.as_ref(), capture),
+            read_lines(stderr, stderr_tail, lines.as_ref(), capture),
+            child.status()
+        );
+
+        write?;
+
+        Ok(Captured {
+            status: status?,
+            stdout: String::from_utf8_lossy(&stdout?).into_owned(),
+            stderr: String::from_utf8_lossy(&stderr?).into_owned(),
+        })
+    }
+}
+
+/// The exit code a shell uses when it cannot find the program.
+const SHELL_NOT_FOUND: i32 = if cfg!(windows) { 9009 } else { 127 };
+
+/// Kill a child that is still running and wait for it to exit.
+async fn stop(child: &mut Child) -> Result<(), std::io::Error> {
+    child.kill()?;
+    child.status().await?;
+    Ok(())
+}
+
+/// Quote an argument so the shell passes it through as a single word.
+fn quote(arg: &str) -> Cow<'_, str> {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+
+    if plain {
+        Cow::Borrowed(arg)
+    } else if cfg!(windows) {
+        Cow::Owned(format!("\"{}\"", arg.replace('"', "\"\"")))
+    } else {
+        Cow::Owned(format!("'{}'", arg.replace('\'', "'\\''")))
+    }
+}
+
+fn exit_reason(status: ExitStatus) -> ExitReason {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return ExitReason::Signal(signal);
+        }
+    }
+
+    ExitReason::Code(status.code().unwrap_or(-1))
+}
+
+/// Read output line by line into the bounded tail. The whole output is only
+/// kept when it is captured.
+async fn read_lines<R: AsyncRead + Unpin>(
+    reader: Option<R>,
+    tail: &Tail,
+    lines: Option<&UnboundedSender<String>>,
+    capture: bool,
+) -> Result<Vec<u8>, std::io::Error> {
+    let mut output = Vec::new();
+    let Some(reader) = reader else {
+        return Ok(output);
+    };
+
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line).await? == 0 {
+            break;
+        }
+        if capture {
+            output.extend_from_slice(&line);
+        }
+
+        let text = String::from_utf8_lossy(&line)
+            .trim_end_matches(['\r', '\n'])
+            .to_string();
+        if let Some(lines) = lines {
+            lines.unbounded_send(text.clone()).ok();
+        }
+        tail.push(text);
+    }
+
+    Ok(output)
 }
 
 impl<E: Asger> crate::command::Command<E> for Process {
@@ -30,43 +456,96 @@ impl<E: Asger> crate::command::Command<E> for Process {
         path: &'a Path,
     ) -> impl std::future::Future<Output = Result<(), GeenieError>> + 'a {
         async move {
-            let cmd = format!("{} {}", self.cmd, self.args.join(" "));
+            let cmd = self.command_line();
+
+            if let Some(reason) = crate::command::Command::<E>::skip_reason(self) {
+                env.info(&format!("Skipping {}: {}", cmd, reason))
+                    .await
+                    .map_err(GeenieError::backend)?;
+                return Ok(());
+            }
+
+            if self.output {
+                env.info(&format!("Executing {}", cmd))
+                    .await
+                    .map_err(GeenieError::backend)?;
+
+                let (sender, mut receiver) = mpsc::unbounded::<String>();
+                let print = async {
+                    while let Some(line) = receiver.next().await {
+                        env.info(&line).await.map_err(GeenieError::backend)?;
+                    }
+                    Ok::<_, GeenieError>(())
+                };
+
+                let (ret, print) = futures::join!(self.execute(path, Some(sender), false), print);
+                print?;
+                ret?;
+
+                return Ok(());
+            }
 
             let mut spinner = Spinner::new(env);
 
             spinner.start(format!("Executing {}", cmd));
 
-            let ret = Command::new(&self.cmd)
-                .args(&self.args)
-                .current_dir(path)
-                .output()
-                .await;
+            let (sender, mut receiver) = mpsc::unbounded::<String>();
+            let progress = async {
+                while let Some(line) = receiver.next().await {
+                    let line = line.trim();
+                    if !line.is_empty() {
+                        spinner.set_message(format!("Executing {cmd}: {line}"));
+                    }
+                }
+            };
+
+            let (ret, ()) = futures::join!(self.execute(path, Some(sender), false), progress);
 
-            let ret = match ret {
-                Ok(ret) => {
+            match ret {
+                Ok(_) => {
                     spinner.stop(format!("Executed {}", cmd));
-                    ret
+                    Ok(())
                 }
                 Err(err) => {
                     spinner.error(err.to_string());
-                    return Err(GeenieError::backend(err));
+                    Err(err)
                 }
-            };
-
-            if self.output {
-                env.info(&*String::from_utf8_lossy(&ret.stdout))
-                    .await
-                    .map_err(GeenieError::backend)?;
             }
+        }
+    }
 
-            if !ret.status.success() {
-                return Err(GeenieError::command(
-                    String::from_utf8_lossy(&ret.stderr).to_string(),
-                ));
-            }
+    fn describe(&self) -> String {
+        self.command_line()
+    }
 
-            Ok(())
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn dependencies(&self) -> &[String] {
+        &self.after
+    }
+
+    fn is_concurrent(&self) -> bool {
+        self.concurrent
+    }
+
+    fn skip_reason(&self) -> Option<String> {
+        (self.optional && !self.is_available()).then(|| format!("{} is not installed", self.cmd))
+    }
+
+    fn run_detached<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> Option<impl std::future::Future<Output = Result<(), GeenieError>> + 'a> {
+        if self.output || (self.optional && !self.is_available()) {
+            return None;
         }
+
+        Some(async move {
+            self.execute(path, None, false).await?;
+            Ok(())
+        })
     }
 }
 
@@ -83,10 +562,228 @@ impl<E: Asger, C> Item<E, C> for Process {
     }
 }
 
+pub struct CaptureItem<F> {
+    process: Process,
+    then: F,
+}
+
+impl<E, C, F, I> Item<E, C> for CaptureItem<F>
+where
+    F: FnOnce(Captured) -> Result<I, GeenieError> + 'static,
+    I: Item<E, C>,
+{
+    fn process<'a>(
+        self,
+        ctx: crate::Context<'a, E, C>,
+        env: &'a mut Spurgt<E>,
+    ) -> impl std::future::Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            let captured = match ctx.root() {
+                Some(root) => self.process.capture_in(root).await?,
+                None => self.process.capture().await?,
+            };
+            (self.then)(captured)?.process(ctx, env).await
+        }
+    }
+}
+
 pub fn process(cmd: impl ToString) -> Process {
     Process {
         cmd: cmd.to_string(),
         args: Vec::new(),
         output: false,
+        name: None,
+        after: Vec::new(),
+        concurrent: false,
+        envs: Vec::new(),
+        stdin: None,
+        current_dir: None,
+        timeout: None,
+        shell: false,
+        exit_codes: vec![0],
+        tail: 20,
+        optional: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+
+    #[test]
+    fn keeps_only_the_tail_of_uncaptured_output() {
+        let input = (1..=100).map(|n| format!("line {n}\n")).collect::<String>();
+        let tail = Tail::new(3);
+
+        let output = block_on(read_lines(
+            Some(futures::io::Cursor::new(input.as_bytes())),
+            &tail,
+            None,
+            false,
+        ))
+        .unwrap();
+
+        assert!(output.is_empty());
+        assert_eq!(tail.into_lines(), vec!["line 98", "line 99", "line 100"]);
+
+        let tail = Tail::new(0);
+        let output = block_on(read_lines(
+            Some(futures::io::Cursor::new(b"a\r\nb".as_slice())),
+            &tail,
+            None,
+            true,
+        ))
+        .unwrap();
+
+        assert_eq!(output, b"a\r\nb");
+        assert!(tail.into_lines().is_empty());
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn skips_missing_optional_programs() {
+        use crate::{
+            command::{CommandBox, CommandList, CommandStatus, DynamicCommand},
+            testing::TestEnv,
+        };
+
+        let env = TestEnv::new();
+        let list =
+            CommandList::from(vec![
+                Box::new(CommandBox(process("geenie-missing-tool").optional()))
+                    as Box<dyn DynamicCommand<TestEnv>>,
+            ]);
+
+        let report = block_on(list.run_in(&mut Spurgt::new(env.clone()), Path::new("."))).unwrap();
+
+        assert!(matches!(report.commands[0].status, CommandStatus::Skipped));
+        assert_eq!(
+            env.log(),
+            vec!["Skipping geenie-missing-tool: geenie-missing-tool is not installed"]
+        );
+    }
+
+    #[cfg(all(unix, feature = "testing"))]
+    #[test]
+    fn shows_the_latest_line_on_the_spinner() {
+        use crate::{command::Command, testing::TestEnv};
+
+        let env = TestEnv::new();
+        let process = process("sh").arg("-c").arg("echo one; echo; echo two");
+
+        block_on(Command::run(
+            &process,
+            &mut Spurgt::new(env.clone()),
+            Path::new("."),
+        ))
+        .unwrap();
+
+        let cmd = "sh -c 'echo one; echo; echo two'";
+        assert_eq!(
+            env.log(),
+            vec![
+                format!("spinner: Executing {cmd}"),
+                format!("spinner: Executing {cmd}: one"),
+                format!("spinner: Executing {cmd}: two"),
+                format!("spinner done: Executed {cmd}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn quotes_shell_arguments() {
+        assert_eq!(quote("src/main.rs"), "src/main.rs");
+        assert_eq!(quote("--name=demo"), "--name=demo");
+        if cfg!(windows) {
+            assert_eq!(quote("a \"b\""), "\"a \"\"b\"\"\"");
+        } else {
+            assert_eq!(quote(""), "''");
+            assert_eq!(quote("two words"), "'two words'");
+            assert_eq!(quote("a; rm -rf /"), "'a; rm -rf /'");
+            assert_eq!(quote("it's"), "'it'\\''s'");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn shell_mode_passes_arguments_as_single_words() {
+        let captured = block_on(
+            process("printf '%s|'")
+                .shell(true)
+                .arg("two words")
+                .arg("x; echo injected")
+                .arg("it's")
+                .capture(),
+        )
+        .unwrap();
+
+        assert_eq!(captured.stdout, "two words|x; echo injected|it's|");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reports_missing_programs_in_both_modes() {
+        for shell in [false, true] {
+            let err = block_on(process("geenie-missing-tool").shell(shell).capture()).unwrap_err();
+
+            assert!(
+                matches!(&err, GeenieError::ProgramNotFound { program, .. } if program == "geenie-missing-tool"),
+                "{err:?}"
+            );
+            assert!(err.is_tool_failure());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stops_the_child_on_timeout() {
+        let started = Instant::now();
+        let err = block_on(
+            process("sleep")
+                .arg("10")
+                .timeout(Duration::from_millis(100))
+                .capture(),
+        )
+        .unwrap_err();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(
+            matches!(&err, GeenieError::Process(failure) if matches!(failure.reason, ExitReason::TimedOut(_))),
+            "{err:?}"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn returns_wait_errors() {
+        let err = block_on(process("true").stdin(vec![0; 1 << 20]).capture()).unwrap_err();
+
+        assert!(err.is_io(), "{err:?}");
+    }
+
+    #[cfg(all(unix, feature = "testing"))]
+    #[test]
+    fn captures_in_the_generator_root() {
+        use crate::{testing::TestEnv, File};
+
+        let root = std::env::temp_dir().join(format!("geenie-capture-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("marker.txt"), "").unwrap();
+
+        let env = TestEnv::new();
+        let mut geenie = env.geenie::<()>();
+        geenie
+            .root(&root)
+            .push(process("ls").with_output(|captured| Ok(File::new("ls.txt", captured.stdout))));
+        let result = block_on(env.run(geenie, &mut ()));
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let files = result
+            .map(|result| result.files.into_iter().collect::<Vec<_>>())
+            .unwrap();
+        assert_eq!(files, vec![File::new("ls.txt", "marker.txt\n")]);
     }
 }
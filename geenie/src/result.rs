@@ -1,11 +1,18 @@
 use std::collections::BTreeSet;
 
-use relative_path::RelativePathBuf;
+use relative_path::{RelativePath, RelativePathBuf};
 use spurgt::Spurgt;
 
+use crate::answers::Session;
 use crate::command::DynamicCommand;
+#[cfg(feature = "fs")]
+use crate::target::FsTarget;
 use crate::{command::CommandList, FileList};
+use crate::{
+    conflict::WriteReport, plan::Plan, target::Target, transaction::Transaction, ConflictPolicy,
+};
 use crate::{File, GeenieError, Item};
+use spurgt::Asger;
 
 pub(crate) struct ResultBuilder<E> {
     pub(crate) files: Vec<File>,
@@ -26,7 +33,26 @@ impl<E> Default for ResultBuilder<E> {
 impl<E> ResultBuilder<E> {
     pub fn push_file(&mut self, file: File) -> Result<(), GeenieError> {
         if self.seen.contains(&file.path) {
-            return Err(GeenieError::duplicate(file.path.clone()));
+            let existing = self
+                .files
+                .iter_mut()
+                .find(|existing| existing.path == file.path)
+                .ok_or_else(|| GeenieError::duplicate(file.path.clone()))?;
+
+            // Only the incoming file decides whether it may be merged, and
+            // only into a file of the same kind.
+            if existing.kind != file.kind {
+                return Err(GeenieError::duplicate(file.path));
+            }
+
+            existing.content = file
+                .merge
+                .merge(&file.path, &existing.content, &file.content)?;
+            existing.conflict = file.conflict.or(existing.conflict);
+            existing.mode = file.mode.or(existing.mode);
+            existing.merge = file.merge;
+
+            return Ok(());
         }
 
         self.seen.insert(file.path.clone());
@@ -35,15 +61,22 @@ impl<E> ResultBuilder<E> {
         Ok(())
     }
 
+    pub fn take_file(&mut self, path: &RelativePath) -> Option<File> {
+        let index = self.files.iter().position(|file| file.path == path)?;
+        self.seen.remove(path);
+        Some(self.files.remove(index))
+    }
+
     pub fn push_command(&mut self, command: Box<dyn DynamicCommand<E>>) {
         self.commands.push(command);
     }
 
-    pub fn build(self, env: Spurgt<E>) -> GeenieResult<E> {
+    pub fn build(self, env: Spurgt<E>, session: Session) -> GeenieResult<E> {
         GeenieResult {
             files: FileList { files: self.files },
             commands: self.commands.into(),
             env,
+            session,
         }
     }
 }
@@ -67,6 +100,7 @@ pub struct GeenieResult<E> {
     pub env: Spurgt<E>,
     pub files: FileList,
     pub commands: CommandList<E>,
+    pub session: Session,
 }
 
 impl<E> GeenieResult<E> {
@@ -74,12 +108,109 @@ impl<E> GeenieResult<E> {
     pub async fn write_to(
         &mut self,
         path: impl AsRef<std::path::Path>,
-        force: bool,
-    ) -> Result<(), GeenieError> {
-        self.files.write_to(path.as_ref(), force).await?;
-        self.commands.run_in(&mut self.env, path.as_ref()).await?;
+        policy: impl Into<ConflictPolicy>,
+    ) -> Result<WriteReport, GeenieError>
+    where
+        E: Asger,
+    {
+        self.write_into(&FsTarget::new(path.as_ref()), policy).await
+    }
 
-        Ok(())
+    pub async fn write_into<T: Target>(
+        &mut self,
+        target: &T,
+        policy: impl Into<ConflictPolicy>,
+    ) -> Result<WriteReport, GeenieError>
+    where
+        E: Asger,
+    {
+        let root = self.command_root(target)?;
+
+        let report = self.files.write_into(target, policy, &mut self.env).await?;
+
+        if let Some(root) = root {
+            self.commands.run_in(&mut self.env, root).await?;
+        }
+
+        Ok(report)
+    }
+
+    /// Write the files into a [`Transaction`] over `target`, rolling it back
+    /// if a write fails.
+    ///
+    /// Commands are not run here since the transaction cannot restore files
+    /// they change. They run in [`GeenieResult::commit`] once the transaction
+    /// is accepted.
+    pub async fn write_transaction<T: Target>(
+        &mut self,
+        target: T,
+        policy: impl Into<ConflictPolicy>,
+    ) -> Result<(WriteReport, Transaction<T>), GeenieError>
+    where
+        E: Asger,
+    {
+        self.command_root(&target)?;
+
+        let transaction = Transaction::new(target);
+
+        match self
+            .files
+            .write_into(&transaction, policy, &mut self.env)
+            .await
+        {
+            Ok(report) => Ok((report, transaction)),
+            Err(err) => match transaction.rollback().await {
+                Ok(_) => Err(err),
+                Err(rollback) => Err(GeenieError::rollback_failed(err, rollback)),
+            },
+        }
+    }
+
+    /// Commit a transaction from [`GeenieResult::write_transaction`] and run
+    /// the commands in its root.
+    pub async fn commit<T: Target>(&mut self, transaction: Transaction<T>) -> Result<T, GeenieError>
+    where
+        E: Asger,
+    {
+        let target = transaction.commit();
+
+        if let Some(root) = self.command_root(&target)? {
+            self.commands.run_in(&mut self.env, root).await?;
+        }
+
+        Ok(target)
+    }
+
+    fn command_root<'t, T: Target>(
+        &self,
+        target: &'t T,
+    ) -> Result<Option<&'t std::path::Path>, GeenieError> {
+        match target.root() {
+            None if !self.commands.is_empty() => Err(GeenieError::NoCommandRoot {
+                commands: self.commands.describe(),
+            }),
+            root => Ok(root),
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    pub async fn dry_run(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        policy: impl Into<ConflictPolicy>,
+    ) -> Result<Plan, GeenieError> {
+        self.plan(&FsTarget::new(path.as_ref()), policy).await
+    }
+
+    pub async fn plan<T: Target>(
+        &self,
+        target: &T,
+        policy: impl Into<ConflictPolicy>,
+    ) -> Result<Plan, GeenieError> {
+        Ok(Plan {
+            files: self.files.plan(target, policy).await?,
+            commands: self.commands.describe(),
+        })
     }
 }
 
@@ -96,3 +227,130 @@ impl<E: 'static, C> Item<E, C> for GeenieResult<E> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MergeStrategy;
+
+    #[cfg(feature = "testing")]
+    struct Noop;
+
+    #[cfg(feature = "testing")]
+    impl<E> crate::Command<E> for Noop {
+        fn run<'a>(
+            &'a self,
+            _env: &'a mut Spurgt<E>,
+            _path: &'a std::path::Path,
+        ) -> impl std::future::Future<Output = Result<(), GeenieError>> + 'a {
+            async { Ok(()) }
+        }
+
+        fn describe(&self) -> String {
+            String::from("noop")
+        }
+    }
+
+    #[test]
+    fn duplicate_paths_merge_with_the_incoming_strategy() {
+        let mut builder = ResultBuilder::<()>::default();
+        builder.push_file(File::new("a.txt", "a\n")).unwrap();
+        builder
+            .push_file(File::new("a.txt", "b\n").on_duplicate(MergeStrategy::Append))
+            .unwrap();
+        assert_eq!(builder.files[0].content, b"a\nb\n");
+
+        assert!(matches!(
+            builder.push_file(File::new("a.txt", "c\n")),
+            Err(GeenieError::Duplicate { .. })
+        ));
+        assert_eq!(builder.files[0].content, b"a\nb\n");
+
+        builder.push_file(File::new("b.txt", "b")).unwrap();
+        assert!(matches!(
+            builder.push_file(File::new("b.txt", "b")),
+            Err(GeenieError::Duplicate { .. })
+        ));
+    }
+
+    #[test]
+    fn duplicate_paths_of_another_kind_are_not_merged() {
+        let mut builder = ResultBuilder::<()>::default();
+        builder.push_file(File::new("link", "a\n")).unwrap();
+
+        assert!(matches!(
+            builder.push_file(File::symlink("link", "a.txt").on_duplicate(MergeStrategy::Append)),
+            Err(GeenieError::Duplicate { .. })
+        ));
+        assert_eq!(builder.files, vec![File::new("link", "a\n")]);
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn commands_require_a_root() {
+        use crate::{command::CommandBox, MemoryTarget};
+        use futures::executor::block_on;
+
+        let mut builder = ResultBuilder::default();
+        builder.push_file(File::new("a.txt", "a")).unwrap();
+        builder.push_command(Box::new(CommandBox(Noop)));
+        let mut result = builder.build(
+            Spurgt::new(crate::testing::TestEnv::new()),
+            Session::default(),
+        );
+
+        let target = MemoryTarget::new();
+        let err = block_on(result.write_into(&target, ConflictPolicy::Fail)).unwrap_err();
+
+        assert!(
+            matches!(&err, GeenieError::NoCommandRoot { commands } if commands == &["noop"]),
+            "{err:?}"
+        );
+        assert!(target.files().is_empty());
+    }
+
+    #[cfg(all(feature = "testing", feature = "fs"))]
+    #[test]
+    fn transactions_run_commands_after_commit() {
+        use crate::{command::CommandBox, FsTarget};
+        use futures::executor::block_on;
+
+        struct Touch;
+
+        impl<E> crate::Command<E> for Touch {
+            fn run<'a>(
+                &'a self,
+                _env: &'a mut Spurgt<E>,
+                path: &'a std::path::Path,
+            ) -> impl std::future::Future<Output = Result<(), GeenieError>> + 'a {
+                async move { Ok(std::fs::write(path.join("ran"), "")?) }
+            }
+        }
+
+        let root = std::env::temp_dir().join(format!("geenie-commit-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+
+        let mut builder = ResultBuilder::default();
+        builder.push_file(File::new("a.txt", "a")).unwrap();
+        builder.push_command(Box::new(CommandBox(Touch)));
+        let mut result = builder.build(
+            Spurgt::new(crate::testing::TestEnv::new()),
+            Session::default(),
+        );
+
+        block_on(async {
+            let (_, transaction) = result
+                .write_transaction(FsTarget::new(&root), ConflictPolicy::Fail)
+                .await
+                .unwrap();
+            assert!(root.join("a.txt").exists());
+            assert!(!root.join("ran").exists());
+
+            result.commit(transaction).await.unwrap();
+        });
+
+        assert!(root.join("ran").exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
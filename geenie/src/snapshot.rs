@@ -0,0 +1,187 @@
+use core::fmt::{self, Write};
+
+use relative_path::RelativePath;
+use spurgt::Asger;
+
+use crate::{
+    diff::{diff, is_binary},
+    result::GeenieResult,
+    ConflictPolicy, GeenieError, MemoryEntry, MemoryTarget,
+};
+
+pub const BLESS_VAR: &str = "GEENIE_BLESS";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    content: String,
+}
+
+impl Snapshot {
+    pub async fn capture<E: Asger>(result: &mut GeenieResult<E>) -> Result<Snapshot, GeenieError> {
+        let target = MemoryTarget::new();
+        result
+            .files
+            .write_into(&target, ConflictPolicy::Fail, &mut result.env)
+            .await?;
+        Ok(Snapshot::new(&target, &result.commands.describe()))
+    }
+
+    pub fn new(target: &MemoryTarget, commands: &[String]) -> Snapshot {
+        let mut content = String::new();
+
+        for (path, entry) in target.entries() {
+            match entry {
+                MemoryEntry::Dir => {
+                    writeln!(content, "== {path}/").ok();
+                }
+                MemoryEntry::Symlink { target } => {
+                    writeln!(content, "== {path} -> {target}").ok();
+                }
+                MemoryEntry::File {
+                    content: file,
+                    mode,
+                } if is_binary(&file) => {
+                    writeln!(content, "== {path} ({mode:04o}, binary)").ok();
+                    for chunk in file.chunks(32) {
+                        for byte in chunk {
+                            write!(content, "{byte:02x}").ok();
+                        }
+                        content.push('\n');
+                    }
+                }
+                MemoryEntry::File {
+                    content: file,
+                    mode,
+                } => {
+                    writeln!(content, "== {path} ({mode:04o})").ok();
+                    let text = String::from_utf8_lossy(&file);
+                    content.push_str(&text);
+                    if !text.is_empty() && !text.ends_with('\n') {
+                        content.push_str("\n\\ No newline at end of file\n");
+                    }
+                }
+            }
+        }
+
+        for command in commands {
+            writeln!(content, "$ {command}").ok();
+        }
+
+        Snapshot { content }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.content
+    }
+
+    pub async fn assert_matches(&self, path: impl AsRef<std::path::Path>) {
+        self.check(path.as_ref(), is_blessing()).await
+    }
+
+    async fn check(&self, path: &std::path::Path, bless: bool) {
+        if bless {
+            if let Some(parent) = path.parent() {
+                async_fs::create_dir_all(parent)
+                    .await
+                    .unwrap_or_else(|err| panic!("could not create {}: {err}", parent.display()));
+            }
+            async_fs::write(path, &self.content)
+                .await
+                .unwrap_or_else(|err| panic!("could not write {}: {err}", path.display()));
+            return;
+        }
+
+        let expected = match async_fs::read(path).await {
+            Ok(expected) => expected,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => panic!(
+                "snapshot {} does not exist, rerun with {BLESS_VAR}=1 to create it",
+                path.display()
+            ),
+            Err(err) => panic!("could not read {}: {err}", path.display()),
+        };
+
+        let name = RelativePath::new(
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or("snapshot"),
+        );
+        let changes = diff(name, &expected, self.content.as_bytes());
+        if !changes.is_unchanged() {
+            panic!(
+                "snapshot {} does not match, rerun with {BLESS_VAR}=1 to update it\n{changes}",
+                path.display()
+            );
+        }
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.content)
+    }
+}
+
+fn is_blessing() -> bool {
+    std::env::var(BLESS_VAR).is_ok_and(|value| !value.is_empty() && value != "0")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::Target;
+
+    #[test]
+    fn lists_every_entry() {
+        let target = MemoryTarget::new()
+            .with_file("src/lib.rs", "pub fn a() {}")
+            .with_file("logo.bin", [0u8, 1, 255])
+            .with_file("README.md", "# demo\n");
+        block_on(target.symlink("latest".as_ref(), "src/lib.rs")).unwrap();
+
+        let snapshot = Snapshot::new(&target, &[String::from("cargo fmt")]);
+
+        assert_eq!(
+            snapshot.as_str(),
+            "== README.md (0644)\n\
+             # demo\n\
+             == latest -> src/lib.rs\n\
+             == logo.bin (0644, binary)\n\
+             0001ff\n\
+             == src/\n\
+             == src/lib.rs (0644)\n\
+             pub fn a() {}\n\
+             \\ No newline at end of file\n\
+             $ cargo fmt\n"
+        );
+    }
+
+    #[test]
+    fn blesses_then_compares() {
+        let root = std::env::temp_dir().join(format!("geenie-snapshot-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let path = root.join("snapshots/demo.snap");
+        let snapshot = Snapshot::new(&MemoryTarget::new().with_file("a.txt", "a\n"), &[]);
+
+        let missing = catch_unwind(AssertUnwindSafe(|| block_on(snapshot.check(&path, false))));
+
+        block_on(snapshot.check(&path, true));
+        let written = std::fs::read_to_string(&path).unwrap();
+        block_on(snapshot.check(&path, false));
+
+        std::fs::write(&path, "== a.txt (0644)\nb\n").unwrap();
+        let mismatch = catch_unwind(AssertUnwindSafe(|| block_on(snapshot.check(&path, false))));
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let message =
+            |result: std::thread::Result<()>| *result.unwrap_err().downcast::<String>().unwrap();
+        assert!(message(missing).contains("does not exist, rerun with GEENIE_BLESS=1"));
+        assert_eq!(written, "== a.txt (0644)\na\n");
+        let mismatch = message(mismatch);
+        assert!(mismatch.contains("does not match"), "{mismatch}");
+        assert!(mismatch.contains("-b\n+a\n"), "{mismatch}");
+    }
+}
@@ -0,0 +1,917 @@
+use std::{collections::BTreeMap, future::Future, sync::Mutex};
+
+use relative_path::{RelativePath, RelativePathBuf};
+
+use crate::GeenieError;
+
+pub trait Target {
+    fn read<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<Option<Vec<u8>>, GeenieError>> + 'a;
+
+    fn write<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        content: &'a [u8],
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a;
+
+    fn write_mode<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        content: &'a [u8],
+        mode: u32,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            self.write(path, content).await?;
+            self.set_mode(path, mode).await
+        }
+    }
+
+    fn create_dir<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a;
+
+    fn create_dir_mode<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        mode: u32,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            self.create_dir(path).await?;
+            self.set_mode(path, mode).await
+        }
+    }
+
+    fn set_mode<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        mode: u32,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a;
+
+    fn symlink<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        target: &'a str,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a;
+
+    fn exists<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<bool, GeenieError>> + 'a;
+
+    fn remove<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a;
+
+    /// Remove a path and, if it is a directory, everything below it.
+    fn remove_all<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        self.remove(path)
+    }
+
+    /// The permission bits of an existing file, if the target tracks them.
+    fn read_mode<'a>(
+        &'a self,
+        _path: &'a RelativePath,
+    ) -> impl Future<Output = Result<Option<u32>, GeenieError>> + 'a {
+        async move { Ok(None) }
+    }
+
+    /// The link text of an existing symlink.
+    fn read_link<'a>(
+        &'a self,
+        _path: &'a RelativePath,
+    ) -> impl Future<Output = Result<Option<String>, GeenieError>> + 'a {
+        async move { Ok(None) }
+    }
+
+    fn root(&self) -> Option<&std::path::Path> {
+        None
+    }
+}
+
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone)]
+pub struct FsTarget {
+    root: std::path::PathBuf,
+}
+
+#[cfg(feature = "fs")]
+impl FsTarget {
+    pub fn new(root: impl Into<std::path::PathBuf>) -> FsTarget {
+        FsTarget { root: root.into() }
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Target for FsTarget {
+    fn read<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<Option<Vec<u8>>, GeenieError>> + 'a {
+        async move {
+            match async_fs::read(path.to_logical_path(&self.root)).await {
+                Ok(content) => Ok(Some(content)),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err.into()),
+            }
+        }
+    }
+
+    fn write<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        content: &'a [u8],
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            let file_path = path.to_logical_path(&self.root);
+            if let Some(parent) = file_path.parent() {
+                async_fs::create_dir_all(parent).await?;
+            }
+            async_fs::write(file_path, content).await?;
+            Ok(())
+        }
+    }
+
+    fn create_dir<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            async_fs::create_dir_all(path.to_logical_path(&self.root)).await?;
+            Ok(())
+        }
+    }
+
+    fn set_mode<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        mode: u32,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+
+                async_fs::set_permissions(
+                    path.to_logical_path(&self.root),
+                    std::fs::Permissions::from_mode(mode),
+                )
+                .await?;
+            }
+
+            #[cfg(not(unix))]
+            let _ = (path, mode);
+
+            Ok(())
+        }
+    }
+
+    fn symlink<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        target: &'a str,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            let link = path.to_logical_path(&self.root);
+            if let Some(parent) = link.parent() {
+                async_fs::create_dir_all(parent).await?;
+            }
+
+            match async_fs::symlink_metadata(&link).await {
+                Ok(meta) if meta.is_dir() => {
+                    return Err(GeenieError::exists(path.to_relative_path_buf()))
+                }
+                Ok(_) => async_fs::remove_file(&link).await?,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            }
+
+            #[cfg(unix)]
+            async_fs::unix::symlink(target, &link).await?;
+
+            #[cfg(windows)]
+            async_fs::windows::symlink_file(target, &link).await?;
+
+            Ok(())
+        }
+    }
+
+    fn exists<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<bool, GeenieError>> + 'a {
+        async move {
+            match async_fs::symlink_metadata(path.to_logical_path(&self.root)).await {
+                Ok(_) => Ok(true),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+                Err(err) => Err(err.into()),
+            }
+        }
+    }
+
+    fn remove<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            let file_path = path.to_logical_path(&self.root);
+            let result = match async_fs::symlink_metadata(&file_path).await {
+                Ok(meta) if meta.is_dir() => async_fs::remove_dir(&file_path).await,
+                Ok(_) => async_fs::remove_file(&file_path).await,
+                Err(err) => Err(err),
+            };
+
+            match result {
+                Ok(()) => Ok(()),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(err) => Err(err.into()),
+            }
+        }
+    }
+
+    fn remove_all<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            let file_path = path.to_logical_path(&self.root);
+            let result = match async_fs::symlink_metadata(&file_path).await {
+                Ok(meta) if meta.is_dir() => async_fs::remove_dir_all(&file_path).await,
+                Ok(_) => async_fs::remove_file(&file_path).await,
+                Err(err) => Err(err),
+            };
+
+            match result {
+                Ok(()) => Ok(()),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(err) => Err(err.into()),
+            }
+        }
+    }
+
+    fn read_mode<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<Option<u32>, GeenieError>> + 'a {
+        async move {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+
+                match async_fs::metadata(path.to_logical_path(&self.root)).await {
+                    Ok(meta) => Ok(Some(meta.permissions().mode() & 0o7777)),
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                    Err(err) => Err(err.into()),
+                }
+            }
+
+            #[cfg(not(unix))]
+            {
+                let _ = path;
+                Ok(None)
+            }
+        }
+    }
+
+    fn read_link<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<Option<String>, GeenieError>> + 'a {
+        async move {
+            match async_fs::read_link(path.to_logical_path(&self.root)).await {
+                Ok(target) => Ok(Some(target.to_string_lossy().into_owned())),
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        std::io::ErrorKind::NotFound | std::io::ErrorKind::InvalidInput
+                    ) =>
+                {
+                    Ok(None)
+                }
+                Err(err) => Err(err.into()),
+            }
+        }
+    }
+
+    fn root(&self) -> Option<&std::path::Path> {
+        Some(&self.root)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemoryEntry {
+    File { content: Vec<u8>, mode: u32 },
+    Symlink { target: String },
+    Dir,
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryTarget {
+    entries: Mutex<BTreeMap<RelativePathBuf, MemoryEntry>>,
+}
+
+impl MemoryTarget {
+    pub fn new() -> MemoryTarget {
+        MemoryTarget::default()
+    }
+
+    pub fn with_file(self, path: impl Into<RelativePathBuf>, content: impl Into<Vec<u8>>) -> Self {
+        self.insert(
+            path.into(),
+            MemoryEntry::File {
+                content: content.into(),
+                mode: 0o644,
+            },
+        );
+        self
+    }
+
+    pub fn get(&self, path: impl AsRef<RelativePath>) -> Option<Vec<u8>> {
+        match self.lock().get(path.as_ref()) {
+            Some(MemoryEntry::File { content, .. }) => Some(content.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn mode(&self, path: impl AsRef<RelativePath>) -> Option<u32> {
+        match self.lock().get(path.as_ref()) {
+            Some(MemoryEntry::File { mode, .. }) => Some(*mode),
+            _ => None,
+        }
+    }
+
+    pub fn link(&self, path: impl AsRef<RelativePath>) -> Option<String> {
+        match self.lock().get(path.as_ref()) {
+            Some(MemoryEntry::Symlink { target }) => Some(target.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn is_dir(&self, path: impl AsRef<RelativePath>) -> bool {
+        matches!(self.lock().get(path.as_ref()), Some(MemoryEntry::Dir))
+    }
+
+    pub fn files(&self) -> BTreeMap<RelativePathBuf, Vec<u8>> {
+        self.lock()
+            .iter()
+            .filter_map(|(path, entry)| match entry {
+                MemoryEntry::File { content, .. } => Some((path.clone(), content.clone())),
+                MemoryEntry::Symlink { .. } | MemoryEntry::Dir => None,
+            })
+            .collect()
+    }
+
+    pub fn dirs(&self) -> Vec<RelativePathBuf> {
+        self.lock()
+            .iter()
+            .filter(|(_, entry)| matches!(entry, MemoryEntry::Dir))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    pub fn entries(&self) -> BTreeMap<RelativePathBuf, MemoryEntry> {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<RelativePathBuf, MemoryEntry>> {
+        self.entries.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn insert(&self, path: RelativePathBuf, entry: MemoryEntry) {
+        let mut entries = self.lock();
+        let mut parent = path.parent();
+        while let Some(dir) = parent {
+            if !dir.as_str().is_empty() {
+                entries
+                    .entry(dir.to_relative_path_buf())
+                    .or_insert(MemoryEntry::Dir);
+            }
+            parent = dir.parent();
+        }
+        entries.insert(path, entry);
+    }
+}
+
+impl Target for MemoryTarget {
+    fn read<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<Option<Vec<u8>>, GeenieError>> + 'a {
+        async move { Ok(self.get(path)) }
+    }
+
+    fn write<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        content: &'a [u8],
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        let mode = self.mode(path).unwrap_or(0o644);
+        self.write_mode(path, content, mode)
+    }
+
+    fn write_mode<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        content: &'a [u8],
+        mode: u32,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            if self.is_dir(path) {
+                return Err(GeenieError::exists(path.to_relative_path_buf()));
+            }
+            self.insert(
+                path.to_relative_path_buf(),
+                MemoryEntry::File {
+                    content: content.to_vec(),
+                    mode,
+                },
+            );
+            Ok(())
+        }
+    }
+
+    fn create_dir<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            self.insert(path.to_relative_path_buf(), MemoryEntry::Dir);
+            Ok(())
+        }
+    }
+
+    fn set_mode<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        mode: u32,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            match self.lock().get_mut(path) {
+                Some(MemoryEntry::File { mode: current, .. }) => {
+                    *current = mode;
+                    Ok(())
+                }
+                Some(_) => Ok(()),
+                None => Err(GeenieError::backend(format!("{path} does not exist"))),
+            }
+        }
+    }
+
+    fn symlink<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        target: &'a str,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            if self.is_dir(path) {
+                return Err(GeenieError::exists(path.to_relative_path_buf()));
+            }
+            self.insert(
+                path.to_relative_path_buf(),
+                MemoryEntry::Symlink {
+                    target: target.to_string(),
+                },
+            );
+            Ok(())
+        }
+    }
+
+    fn exists<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<bool, GeenieError>> + 'a {
+        async move { Ok(self.lock().contains_key(path)) }
+    }
+
+    fn remove<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            let mut entries = self.lock();
+            let has_children = entries.keys().any(|entry| entry.parent() == Some(path));
+            if has_children {
+                return Err(GeenieError::backend(format!(
+                    "directory {path} is not empty"
+                )));
+            }
+            entries.remove(path);
+            Ok(())
+        }
+    }
+
+    fn remove_all<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            self.lock().retain(|entry, _| !entry.starts_with(path));
+            Ok(())
+        }
+    }
+
+    fn read_mode<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<Option<u32>, GeenieError>> + 'a {
+        async move { Ok(self.mode(path)) }
+    }
+
+    fn read_link<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<Option<String>, GeenieError>> + 'a {
+        async move { Ok(self.link(path)) }
+    }
+}
+
+/// Archive entries are written once, so modes set before an entry is added
+/// are kept until it is written into the entry header.
+#[cfg(any(feature = "tar", feature = "zip"))]
+#[derive(Default)]
+struct ArchiveModes {
+    pending: BTreeMap<RelativePathBuf, u32>,
+    written: std::collections::BTreeSet<RelativePathBuf>,
+}
+
+#[cfg(any(feature = "tar", feature = "zip"))]
+impl ArchiveModes {
+    fn set(&mut self, path: &RelativePath, mode: u32, archive: &str) -> Result<(), GeenieError> {
+        if self.written.contains(path) {
+            return Err(GeenieError::backend(format!(
+                "cannot change the mode of {path} after it was added to a {archive} archive"
+            )));
+        }
+
+        self.pending.insert(path.to_relative_path_buf(), mode);
+        Ok(())
+    }
+
+    fn take(&mut self, path: &RelativePath, mode: Option<u32>, default: u32) -> u32 {
+        self.written.insert(path.to_relative_path_buf());
+        let pending = self.pending.remove(path);
+        mode.or(pending).unwrap_or(default)
+    }
+}
+
+#[cfg(feature = "tar")]
+pub struct TarTarget<W: std::io::Write> {
+    builder: Mutex<tar::Builder<W>>,
+    modes: Mutex<ArchiveModes>,
+}
+
+#[cfg(feature = "tar")]
+impl<W: std::io::Write> TarTarget<W> {
+    pub fn new(writer: W) -> TarTarget<W> {
+        TarTarget {
+            builder: Mutex::new(tar::Builder::new(writer)),
+            modes: Mutex::default(),
+        }
+    }
+
+    pub fn finish(self) -> Result<W, GeenieError> {
+        let builder = self
+            .builder
+            .into_inner()
+            .unwrap_or_else(|err| err.into_inner());
+        Ok(builder.into_inner()?)
+    }
+
+    fn append(
+        &self,
+        path: &RelativePath,
+        kind: tar::EntryType,
+        mode: Option<u32>,
+        content: &[u8],
+    ) -> Result<(), GeenieError> {
+        let default = match kind {
+            tar::EntryType::Directory => 0o755,
+            _ => 0o644,
+        };
+        let mode = self.modes().take(path, mode, default);
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(kind);
+        header.set_size(content.len() as u64);
+        header.set_mode(mode);
+        header.set_mtime(0);
+
+        let mut builder = self.builder.lock().unwrap_or_else(|err| err.into_inner());
+        builder.append_data(&mut header, path.as_str(), content)?;
+        Ok(())
+    }
+
+    fn modes(&self) -> std::sync::MutexGuard<'_, ArchiveModes> {
+        self.modes.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+#[cfg(feature = "tar")]
+impl<W: std::io::Write> Target for TarTarget<W> {
+    fn read<'a>(
+        &'a self,
+        _path: &'a RelativePath,
+    ) -> impl Future<Output = Result<Option<Vec<u8>>, GeenieError>> + 'a {
+        async move { Ok(None) }
+    }
+
+    fn write<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        content: &'a [u8],
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move { self.append(path, tar::EntryType::Regular, None, content) }
+    }
+
+    fn write_mode<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        content: &'a [u8],
+        mode: u32,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move { self.append(path, tar::EntryType::Regular, Some(mode), content) }
+    }
+
+    fn create_dir<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move { self.append(path, tar::EntryType::Directory, None, &[]) }
+    }
+
+    fn create_dir_mode<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        mode: u32,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move { self.append(path, tar::EntryType::Directory, Some(mode), &[]) }
+    }
+
+    fn set_mode<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        mode: u32,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move { self.modes().set(path, mode, "tar") }
+    }
+
+    fn symlink<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        target: &'a str,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_size(0);
+            header.set_mode(0o777);
+            header.set_mtime(0);
+            self.modes().take(path, None, 0o777);
+
+            let mut builder = self.builder.lock().unwrap_or_else(|err| err.into_inner());
+            builder.append_link(&mut header, path.as_str(), target)?;
+            Ok(())
+        }
+    }
+
+    fn exists<'a>(
+        &'a self,
+        _path: &'a RelativePath,
+    ) -> impl Future<Output = Result<bool, GeenieError>> + 'a {
+        async move { Ok(false) }
+    }
+
+    fn remove<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            Err(GeenieError::backend(format!(
+                "cannot remove {path} from a tar archive"
+            )))
+        }
+    }
+}
+
+#[cfg(feature = "zip")]
+pub struct ZipTarget<W: std::io::Write + std::io::Seek> {
+    writer: Mutex<zip::ZipWriter<W>>,
+    modes: Mutex<ArchiveModes>,
+}
+
+#[cfg(feature = "zip")]
+impl<W: std::io::Write + std::io::Seek> ZipTarget<W> {
+    pub fn new(writer: W) -> ZipTarget<W> {
+        ZipTarget {
+            writer: Mutex::new(zip::ZipWriter::new(writer)),
+            modes: Mutex::default(),
+        }
+    }
+
+    pub fn finish(self) -> Result<W, GeenieError> {
+        let writer = self
+            .writer
+            .into_inner()
+            .unwrap_or_else(|err| err.into_inner());
+        writer.finish().map_err(GeenieError::backend)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, zip::ZipWriter<W>> {
+        self.writer.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn modes(&self) -> std::sync::MutexGuard<'_, ArchiveModes> {
+        self.modes.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn start_file(
+        &self,
+        path: &RelativePath,
+        content: &[u8],
+        mode: Option<u32>,
+    ) -> Result<(), GeenieError> {
+        use std::io::Write;
+
+        let mode = self.modes().take(path, mode, 0o644);
+        let options = zip::write::SimpleFileOptions::default().unix_permissions(mode);
+        let mut writer = self.lock();
+        writer
+            .start_file(path.as_str(), options)
+            .map_err(GeenieError::backend)?;
+        writer.write_all(content)?;
+        Ok(())
+    }
+
+    fn add_directory(&self, path: &RelativePath, mode: Option<u32>) -> Result<(), GeenieError> {
+        let mode = self.modes().take(path, mode, 0o755);
+        let options = zip::write::SimpleFileOptions::default().unix_permissions(mode);
+        self.lock()
+            .add_directory(path.as_str(), options)
+            .map_err(GeenieError::backend)
+    }
+}
+
+#[cfg(feature = "zip")]
+impl<W: std::io::Write + std::io::Seek> Target for ZipTarget<W> {
+    fn read<'a>(
+        &'a self,
+        _path: &'a RelativePath,
+    ) -> impl Future<Output = Result<Option<Vec<u8>>, GeenieError>> + 'a {
+        async move { Ok(None) }
+    }
+
+    fn write<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        content: &'a [u8],
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move { self.start_file(path, content, None) }
+    }
+
+    fn write_mode<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        content: &'a [u8],
+        mode: u32,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move { self.start_file(path, content, Some(mode)) }
+    }
+
+    fn create_dir<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move { self.add_directory(path, None) }
+    }
+
+    fn create_dir_mode<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        mode: u32,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move { self.add_directory(path, Some(mode)) }
+    }
+
+    fn set_mode<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        mode: u32,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move { self.modes().set(path, mode, "zip") }
+    }
+
+    fn symlink<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        target: &'a str,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            self.modes().take(path, None, 0o777);
+            self.lock()
+                .add_symlink(
+                    path.as_str(),
+                    target,
+                    zip::write::SimpleFileOptions::default(),
+                )
+                .map_err(GeenieError::backend)
+        }
+    }
+
+    fn exists<'a>(
+        &'a self,
+        _path: &'a RelativePath,
+    ) -> impl Future<Output = Result<bool, GeenieError>> + 'a {
+        async move { Ok(false) }
+    }
+
+    fn remove<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            Err(GeenieError::backend(format!(
+                "cannot remove {path} from a zip archive"
+            )))
+        }
+    }
+}
+
+#[cfg(all(test, any(feature = "tar", feature = "zip")))]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+
+    async fn populate(target: &impl Target) -> Result<(), GeenieError> {
+        target.set_mode("bin/run".as_ref(), 0o755).await?;
+        target.create_dir_mode("bin".as_ref(), 0o700).await?;
+        target.write("bin/run".as_ref(), b"#!/bin/sh").await?;
+        target.write_mode("build.sh".as_ref(), b"", 0o750).await?;
+        target.write("README.md".as_ref(), b"").await
+    }
+
+    #[cfg(feature = "tar")]
+    #[test]
+    fn tar_entries_carry_their_mode() {
+        let target = TarTarget::new(Vec::new());
+        block_on(populate(&target)).unwrap();
+        assert!(block_on(target.set_mode("README.md".as_ref(), 0o755)).is_err());
+
+        let archive = target.finish().unwrap();
+        let mut archive = tar::Archive::new(archive.as_slice());
+        let modes = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                let path = entry.path().unwrap().to_string_lossy().into_owned();
+                (path, entry.header().mode().unwrap())
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            modes,
+            vec![
+                ("bin".into(), 0o700),
+                ("bin/run".into(), 0o755),
+                ("build.sh".into(), 0o750),
+                ("README.md".into(), 0o644),
+            ]
+        );
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn zip_entries_carry_their_mode() {
+        let target = ZipTarget::new(std::io::Cursor::new(Vec::new()));
+        block_on(populate(&target)).unwrap();
+        assert!(block_on(target.set_mode("README.md".as_ref(), 0o755)).is_err());
+
+        let mut archive = zip::ZipArchive::new(target.finish().unwrap()).unwrap();
+        let modes = (0..archive.len())
+            .map(|index| {
+                let entry = archive.by_index(index).unwrap();
+                (
+                    entry.name().trim_end_matches('/').to_string(),
+                    entry.unix_mode().unwrap() & 0o7777,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            modes,
+            vec![
+                ("bin".into(), 0o700),
+                ("bin/run".into(), 0o755),
+                ("build.sh".into(), 0o750),
+                ("README.md".into(), 0o644),
+            ]
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use minijinja::{Environment, UndefinedBehavior, Value};
+use relative_path::RelativePathBuf;
+use serde::Serialize;
+use spurgt::Spurgt;
+
+use crate::{Context, File, GeenieError, Item};
+
+pub struct Template {
+    path: String,
+    source: String,
+    data: Option<Value>,
+}
+
+impl Template {
+    pub fn new(path: impl ToString, source: impl ToString) -> Template {
+        Template {
+            path: path.to_string(),
+            source: source.to_string(),
+            data: None,
+        }
+    }
+
+    pub fn data(mut self, data: impl Serialize) -> Self {
+        self.data = Some(Value::from_serialize(data));
+        self
+    }
+
+    pub fn render(&self, data: impl Serialize) -> Result<File, GeenieError> {
+        let data = match &self.data {
+            Some(data) => data.clone(),
+            None => Value::from_serialize(data),
+        };
+
+        let path = render(&self.path, &self.path, &data)?;
+        let content = render(&path, &self.source, &data)?;
+
+        Ok(File::new(RelativePathBuf::from(path), content))
+    }
+}
+
+impl<E, C: Serialize> Item<E, C> for Template {
+    fn process<'a>(
+        self,
+        mut ctx: Context<'a, E, C>,
+        _env: &'a mut Spurgt<E>,
+    ) -> impl std::future::Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            let file = self.render(ctx.data())?;
+            ctx.file(file)?;
+            Ok(())
+        }
+    }
+}
+
+pub fn template(path: impl ToString, source: impl ToString) -> Template {
+    Template::new(path, source)
+}
+
+pub fn render(name: &str, source: &str, data: &Value) -> Result<String, GeenieError> {
+    environment()
+        .render_named_str(name, source, data)
+        .map_err(|err| GeenieError::template(name, err))
+}
+
+#[cfg(feature = "manifest")]
+pub fn condition(expr: &str, data: &Value) -> Result<bool, GeenieError> {
+    let env = environment();
+    let expression = env
+        .compile_expression(expr)
+        .map_err(|err| GeenieError::template(expr, err))?;
+    let value = expression
+        .eval(data)
+        .map_err(|err| GeenieError::template(expr, err))?;
+    Ok(value.is_true())
+}
+
+fn environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_keep_trailing_newline(true);
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env.add_filter("snake_case", |value: &str| {
+        join(value, "_", str::to_lowercase)
+    });
+    env.add_filter("kebab_case", |value: &str| {
+        join(value, "-", str::to_lowercase)
+    });
+    env.add_filter("screaming_snake_case", |value: &str| {
+        join(value, "_", str::to_uppercase)
+    });
+    env.add_filter("pascal_case", |value: &str| join(value, "", capitalize));
+    env.add_filter("camel_case", |value: &str| {
+        let pascal = join(value, "", capitalize);
+        let mut chars = pascal.chars();
+        match chars.next() {
+            Some(first) => first.to_lowercase().chain(chars).collect(),
+            None => pascal,
+        }
+    });
+    env
+}
+
+fn join(value: &str, sep: &str, case: impl Fn(&str) -> String) -> String {
+    words(value)
+        .iter()
+        .map(|word| case(word))
+        .collect::<Vec<_>>()
+        .join(sep)
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+fn words(value: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut prev: Option<char> = None;
+
+    for c in value.chars() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(core::mem::take(&mut current));
+            }
+            prev = None;
+            continue;
+        }
+
+        if let Some(prev) = prev {
+            if c.is_uppercase() && (prev.is_lowercase() || prev.is_numeric()) && !current.is_empty()
+            {
+                words.push(core::mem::take(&mut current));
+            } else if c.is_lowercase() && prev.is_uppercase() && current.chars().count() > 1 {
+                current.pop();
+                words.push(core::mem::take(&mut current));
+                current.push(prev);
+            }
+        }
+
+        current.push(c);
+        prev = Some(c);
+    }
+
+    if !current.is_empty() {
+        words.push(current);
+    }
+
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data() -> Value {
+        Value::from_serialize(serde_json::json!({ "name": "myApp", "enabled": true }))
+    }
+
+    #[test]
+    fn renders_path_and_content() {
+        let file = Template::new("src/{{ name | snake_case }}.rs", "// {{ name }}\n")
+            .render(serde_json::json!({ "name": "myApp" }))
+            .unwrap();
+
+        assert_eq!(file.path, "src/my_app.rs");
+        assert_eq!(file.content, b"// myApp\n");
+    }
+
+    #[test]
+    fn explicit_data_wins_over_context() {
+        let file = Template::new("a.txt", "{{ name }}")
+            .data(serde_json::json!({ "name": "explicit" }))
+            .render(serde_json::json!({ "name": "context" }))
+            .unwrap();
+
+        assert_eq!(file.content, b"explicit");
+    }
+
+    #[test]
+    fn undefined_variables_are_errors() {
+        let err = render("a.txt", "{{ missing }}", &data()).unwrap_err();
+        assert!(matches!(err, GeenieError::Template { .. }), "{err:?}");
+    }
+
+    #[test]
+    fn case_filters() {
+        let render = |filter: &str, value: &str| {
+            render(
+                "case",
+                &format!("{{{{ value | {filter} }}}}"),
+                &Value::from_serialize(serde_json::json!({ "value": value })),
+            )
+            .unwrap()
+        };
+
+        assert_eq!(render("snake_case", "HelloWorld"), "hello_world");
+        assert_eq!(render("snake_case", "HTTPServer"), "http_server");
+        assert_eq!(render("kebab_case", "my app_name"), "my-app-name");
+        assert_eq!(render("screaming_snake_case", "myApp2Go"), "MY_APP2_GO");
+        assert_eq!(render("pascal_case", "my-app"), "MyApp");
+        assert_eq!(render("camel_case", "my_app"), "myApp");
+        assert_eq!(render("camel_case", ""), "");
+    }
+
+    #[cfg(feature = "manifest")]
+    #[test]
+    fn conditions() {
+        assert!(condition("enabled and name == 'myApp'", &data()).unwrap());
+        assert!(!condition("not enabled", &data()).unwrap());
+        assert!(condition("enabled and", &data()).is_err());
+    }
+}
@@ -0,0 +1,326 @@
+use std::path::PathBuf;
+
+use futures::StreamExt;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use minijinja::Value;
+use relative_path::RelativePathBuf;
+use serde::Serialize;
+use spurgt::Spurgt;
+
+use crate::{template::render, Context, File, FileList, GeenieError, Item};
+
+pub struct TemplateDir {
+    root: PathBuf,
+    ignore_file: String,
+    suffix: String,
+    data: Option<Value>,
+}
+
+impl TemplateDir {
+    pub fn new(root: impl Into<PathBuf>) -> TemplateDir {
+        TemplateDir {
+            root: root.into(),
+            ignore_file: String::from(".geenieignore"),
+            suffix: String::from(".jinja"),
+            data: None,
+        }
+    }
+
+    pub fn ignore_file(mut self, name: impl ToString) -> Self {
+        self.ignore_file = name.to_string();
+        self
+    }
+
+    pub fn template_suffix(mut self, suffix: impl ToString) -> Self {
+        self.suffix = suffix.to_string();
+        self
+    }
+
+    pub fn data(mut self, data: impl Serialize) -> Self {
+        self.data = Some(Value::from_serialize(data));
+        self
+    }
+
+    pub async fn render(&self, data: impl Serialize) -> Result<FileList, GeenieError> {
+        let data = match &self.data {
+            Some(data) => data.clone(),
+            None => Value::from_serialize(data),
+        };
+
+        let ignore = self.load_ignore().await?;
+
+        let mut files = Vec::new();
+        let mut queue = vec![RelativePathBuf::new()];
+
+        while let Some(dir) = queue.pop() {
+            let mut entries = async_fs::read_dir(dir.to_logical_path(&self.root)).await?;
+
+            while let Some(entry) = entries.next().await {
+                let entry = entry?;
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                let path = dir.join(&*name);
+                let file_type = entry.file_type().await?;
+                let is_dir = file_type.is_dir();
+
+                if (dir.as_str().is_empty() && name == self.ignore_file)
+                    || ignore.matched(path.as_str(), is_dir).is_ignore()
+                {
+                    continue;
+                }
+
+                if is_dir {
+                    queue.push(path);
+                    continue;
+                }
+
+                let rendered = render(path.as_str(), path.as_str(), &data)?;
+                if rendered.split('/').any(|segment| segment.trim().is_empty()) {
+                    continue;
+                }
+
+                if file_type.is_symlink() {
+                    let link = async_fs::read_link(entry.path()).await?;
+                    let link = relative_link(&rendered, &link)?;
+                    files.push(File::symlink(rendered, link));
+                    continue;
+                }
+
+                let content = async_fs::read(entry.path()).await?;
+                let (rendered, content) = match self.strip_suffix(&rendered) {
+                    Some(stripped) => {
+                        let source = String::from_utf8(content).map_err(|_| {
+                            GeenieError::backend(format!("template {path} is not valid utf-8"))
+                        })?;
+                        let content = render(path.as_str(), &source, &data)?;
+                        (stripped.to_string(), content.into_bytes())
+                    }
+                    None => (rendered, content),
+                };
+
+                let mut file = File::new(rendered, content);
+                if let Some(mode) = executable_mode(&entry).await? {
+                    file = file.mode(mode);
+                }
+
+                files.push(file);
+            }
+        }
+
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(files.into())
+    }
+
+    fn strip_suffix<'a>(&self, path: &'a str) -> Option<&'a str> {
+        if self.suffix.is_empty() {
+            return None;
+        }
+        path.strip_suffix(self.suffix.as_str())
+            .filter(|stripped| !stripped.is_empty() && !stripped.ends_with('/'))
+    }
+
+    async fn load_ignore(&self) -> Result<Gitignore, GeenieError> {
+        let mut builder = GitignoreBuilder::new(&self.root);
+
+        match async_fs::read_to_string(self.root.join(&self.ignore_file)).await {
+            Ok(content) => {
+                for line in content.lines() {
+                    builder.add_line(None, line).map_err(GeenieError::backend)?;
+                }
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        builder.build().map_err(GeenieError::backend)
+    }
+}
+
+impl<E, C: Serialize> Item<E, C> for TemplateDir {
+    fn process<'a>(
+        self,
+        mut ctx: Context<'a, E, C>,
+        _env: &'a mut Spurgt<E>,
+    ) -> impl std::future::Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            let files = self.render(ctx.data()).await?;
+            for file in files {
+                ctx.file(file)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The link text of a symlink at `path`, which must stay inside the output.
+fn relative_link(path: &str, link: &std::path::Path) -> Result<String, GeenieError> {
+    use std::path::Component;
+
+    let outside = || {
+        GeenieError::backend(format!(
+            "symlink {path} points outside the template: {}",
+            link.display()
+        ))
+    };
+
+    let mut depth = path.split('/').count() - 1;
+    let mut segments = Vec::new();
+    for component in link.components() {
+        match component {
+            Component::Normal(segment) => {
+                depth += 1;
+                segments.push(segment.to_string_lossy());
+            }
+            Component::ParentDir => {
+                depth = depth.checked_sub(1).ok_or_else(outside)?;
+                segments.push("..".into());
+            }
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) => return Err(outside()),
+        }
+    }
+
+    Ok(segments.join("/"))
+}
+
+#[cfg(unix)]
+async fn executable_mode(entry: &async_fs::DirEntry) -> Result<Option<u32>, GeenieError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = entry.metadata().await?.permissions().mode() & 0o777;
+    Ok((mode & 0o111 != 0).then_some(mode))
+}
+
+#[cfg(not(unix))]
+async fn executable_mode(_entry: &async_fs::DirEntry) -> Result<Option<u32>, GeenieError> {
+    Ok(None)
+}
+
+pub fn template_dir(root: impl Into<PathBuf>) -> TemplateDir {
+    TemplateDir::new(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use std::path::Path;
+
+    use super::*;
+
+    fn skeleton(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("geenie-template-dir-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for (path, content) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        root
+    }
+
+    fn render(root: &Path, dir: TemplateDir) -> Vec<(String, String)> {
+        let files = block_on(dir.render(serde_json::json!({ "name": "demo" }))).unwrap();
+        std::fs::remove_dir_all(root).unwrap();
+        files
+            .into_iter()
+            .map(|file| {
+                (
+                    file.path.to_string(),
+                    String::from_utf8(file.content).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn renders_only_suffixed_files() {
+        let root = skeleton(
+            "suffix",
+            &[
+                ("README.md.jinja", "# {{ name }}\n"),
+                ("ci.yml", "run: ${{ matrix.os }}\n"),
+                ("src/{{ name }}.rs", "{% raw %}\n"),
+            ],
+        );
+
+        assert_eq!(
+            render(&root, TemplateDir::new(&root)),
+            vec![
+                ("README.md".into(), "# demo\n".into()),
+                ("ci.yml".into(), "run: ${{ matrix.os }}\n".into()),
+                ("src/demo.rs".into(), "{% raw %}\n".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn custom_suffix() {
+        let root = skeleton("custom", &[("a.txt.tmpl", "{{ name }}"), ("b.jinja", "x")]);
+
+        assert_eq!(
+            render(&root, TemplateDir::new(&root).template_suffix(".tmpl")),
+            vec![
+                ("a.txt".into(), "demo".into()),
+                ("b.jinja".into(), "x".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_links_inside_the_template() {
+        let link = |path, link: &str| relative_link(path, Path::new(link));
+
+        assert_eq!(
+            link("README", "docs/./README.md").unwrap(),
+            "docs/README.md"
+        );
+        assert_eq!(link("a/b/c", "../../d").unwrap(), "../../d");
+        assert!(link("a/b", "../../d").is_err());
+        assert!(link("a", "b/../../c").is_err());
+        assert!(link("a", "/etc/passwd").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_escaping_symlinks() {
+        let root = skeleton("links", &[("docs/guide.md", "x")]);
+        std::os::unix::fs::symlink("docs/guide.md", root.join("GUIDE.md")).unwrap();
+
+        let files = block_on(TemplateDir::new(&root).render(())).unwrap();
+        let link = files
+            .into_iter()
+            .find(|file| file.path == "GUIDE.md")
+            .unwrap();
+        assert_eq!(link.kind, crate::FileKind::Symlink("docs/guide.md".into()));
+
+        std::os::unix::fs::symlink("/etc/hosts", root.join("hosts")).unwrap();
+        let err = block_on(TemplateDir::new(&root).render(()))
+            .map(drop)
+            .unwrap_err();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(err
+            .to_string()
+            .contains("hosts points outside the template"));
+    }
+
+    #[test]
+    fn ignores_files_and_empty_names() {
+        let root = skeleton(
+            "ignore",
+            &[
+                (".geenieignore", "target/\n"),
+                ("target/out", "x"),
+                ("{% if false %}skip{% endif %}/a.txt", "x"),
+                ("keep.txt", "x"),
+            ],
+        );
+
+        assert_eq!(
+            render(&root, TemplateDir::new(&root)),
+            vec![("keep.txt".into(), "x".into())]
+        );
+    }
+}
@@ -0,0 +1,396 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use serde::Serialize;
+use spurgt::core::{
+    Asger, Confirm, Env, Error, Input, MultiSelect, Password, Select, SpinnerEvent,
+};
+
+pub use crate::snapshot::{Snapshot, BLESS_VAR};
+use crate::{
+    answers::Answers, result::GeenieResult, ConflictPolicy, Geenie, GeenieError, Item, MemoryTarget,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Reply {
+    Input(String),
+    Password(String),
+    Confirm(bool),
+    Select(usize),
+    MultiSelect(Vec<usize>),
+}
+
+impl Reply {
+    fn kind(&self) -> &'static str {
+        match self {
+            Reply::Input(_) => "input",
+            Reply::Password(_) => "password",
+            Reply::Confirm(_) => "confirm",
+            Reply::Select(_) => "select",
+            Reply::MultiSelect(_) => "multi_select",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Scripted {
+    message: String,
+    reply: Reply,
+}
+
+/// A non-interactive environment for tests.
+///
+/// Questions asked through the env are answered in order from a queue of
+/// scripted replies, and every question has to match the next reply by kind
+/// and message. An unexpected question is an error, and dropping the last
+/// clone of the env with replies left over panics. Everything passed to
+/// `Asger::info` and the spinner ends up in [`TestEnv::log`].
+#[derive(Debug, Clone, Default)]
+pub struct TestEnv {
+    answers: Answers,
+    queue: Arc<Mutex<VecDeque<Scripted>>>,
+    log: Arc<Mutex<Vec<String>>>,
+}
+
+impl TestEnv {
+    pub fn new() -> TestEnv {
+        TestEnv::default()
+    }
+
+    pub fn answer(mut self, id: impl ToString, value: impl Serialize) -> Self {
+        self.answers
+            .set(id.to_string(), value)
+            .expect("scripted answer must serialize");
+        self
+    }
+
+    pub fn answers(mut self, answers: Answers) -> Self {
+        let strict = self.answers.is_strict() || answers.is_strict();
+        self.answers.extend(answers);
+        self.answers = std::mem::take(&mut self.answers).strict(strict);
+        self
+    }
+
+    pub fn expect_input(self, message: impl ToString, value: impl ToString) -> Self {
+        self.expect(message, Reply::Input(value.to_string()))
+    }
+
+    pub fn expect_password(self, message: impl ToString, value: impl ToString) -> Self {
+        self.expect(message, Reply::Password(value.to_string()))
+    }
+
+    pub fn expect_confirm(self, message: impl ToString, value: bool) -> Self {
+        self.expect(message, Reply::Confirm(value))
+    }
+
+    pub fn expect_select(self, message: impl ToString, index: usize) -> Self {
+        self.expect(message, Reply::Select(index))
+    }
+
+    pub fn expect_multi_select(
+        self,
+        message: impl ToString,
+        indices: impl IntoIterator<Item = usize>,
+    ) -> Self {
+        self.expect(message, Reply::MultiSelect(indices.into_iter().collect()))
+    }
+
+    /// Messages of the scripted questions that have not been asked yet.
+    pub fn remaining(&self) -> Vec<String> {
+        self.queue()
+            .iter()
+            .map(|scripted| scripted.message.clone())
+            .collect()
+    }
+
+    pub fn log(&self) -> Vec<String> {
+        self.lock().clone()
+    }
+
+    pub fn geenie<C>(&self) -> Geenie<TestEnv, C> {
+        let mut geenie = Geenie::new(self.clone());
+        geenie.answers(self.answers.clone());
+        geenie
+    }
+
+    pub async fn run<C>(
+        &self,
+        mut geenie: Geenie<TestEnv, C>,
+        context: &mut C,
+    ) -> Result<GeenieResult<TestEnv>, GeenieError> {
+        geenie.answers(self.answers.clone());
+
+        let result = geenie.run(context).await?;
+
+        let unused = self
+            .answers
+            .iter()
+            .filter(|(id, _)| result.session.get(id).is_none())
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+
+        if !unused.is_empty() {
+            return Err(GeenieError::backend(format!(
+                "scripted answers were never asked: {}",
+                unused.join(", ")
+            )));
+        }
+
+        Ok(result)
+    }
+
+    pub async fn run_item<C, T>(
+        &self,
+        item: T,
+        context: &mut C,
+    ) -> Result<GeenieResult<TestEnv>, GeenieError>
+    where
+        T: Item<TestEnv, C> + 'static,
+    {
+        let mut geenie = self.geenie();
+        geenie.push(item);
+        self.run(geenie, context).await
+    }
+
+    pub async fn render<C, T>(&self, item: T, context: &mut C) -> Result<MemoryTarget, GeenieError>
+    where
+        T: Item<TestEnv, C> + 'static,
+    {
+        let mut result = self.run_item(item, context).await?;
+        let target = MemoryTarget::new();
+        result
+            .files
+            .write_into(&target, ConflictPolicy::Fail, &mut result.env)
+            .await?;
+        Ok(target)
+    }
+
+    pub async fn snapshot<C, T>(&self, item: T, context: &mut C) -> Result<Snapshot, GeenieError>
+    where
+        T: Item<TestEnv, C> + 'static,
+    {
+        let mut result = self.run_item(item, context).await?;
+        Snapshot::capture(&mut result).await
+    }
+
+    fn expect(self, message: impl ToString, reply: Reply) -> Self {
+        self.queue().push_back(Scripted {
+            message: message.to_string(),
+            reply,
+        });
+        self
+    }
+
+    fn reply(&self, kind: &'static str, message: &str) -> Result<Reply, Error> {
+        let Some(scripted) = self.queue().pop_front() else {
+            return Err(Error::new(GeenieError::backend(format!(
+                "unexpected {kind} question: {message}"
+            ))));
+        };
+
+        if scripted.message != message || scripted.reply.kind() != kind {
+            return Err(Error::new(GeenieError::backend(format!(
+                "expected {} question `{}` but got {kind} question `{message}`",
+                scripted.reply.kind(),
+                scripted.message
+            ))));
+        }
+
+        self.lock().push(message.to_string());
+
+        Ok(scripted.reply)
+    }
+
+    fn queue(&self) -> std::sync::MutexGuard<'_, VecDeque<Scripted>> {
+        self.queue.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<String>> {
+        self.log.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl Drop for TestEnv {
+    fn drop(&mut self) {
+        if Arc::strong_count(&self.queue) > 1 || std::thread::panicking() {
+            return;
+        }
+
+        let remaining = self.remaining();
+        assert!(
+            remaining.is_empty(),
+            "scripted questions were never asked: {}",
+            remaining.join(", ")
+        );
+    }
+}
+
+impl Env for TestEnv {}
+
+impl Asger for TestEnv {
+    async fn info(&mut self, msg: &str) -> Result<(), Error> {
+        self.lock().push(msg.to_string());
+        Ok(())
+    }
+
+    async fn input(&mut self, question: &Input) -> Result<String, Error> {
+        match self.reply("input", &question.message)? {
+            Reply::Input(value) if value.is_empty() => {
+                Ok(question.default.clone().unwrap_or_default())
+            }
+            Reply::Input(value) => Ok(value),
+            _ => unreachable!(),
+        }
+    }
+
+    async fn password(&mut self, question: &Password) -> Result<String, Error> {
+        match self.reply("password", &question.message)? {
+            Reply::Password(value) => Ok(value),
+            _ => unreachable!(),
+        }
+    }
+
+    async fn confirm(&mut self, question: &Confirm) -> Result<bool, Error> {
+        match self.reply("confirm", &question.message)? {
+            Reply::Confirm(value) => Ok(value),
+            _ => unreachable!(),
+        }
+    }
+
+    async fn select(&mut self, question: &Select) -> Result<usize, Error> {
+        match self.reply("select", &question.message)? {
+            Reply::Select(index) => {
+                choice(&question.message, question.items.len(), index)?;
+                Ok(index)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    async fn multi_select(&mut self, question: &MultiSelect) -> Result<Vec<usize>, Error> {
+        match self.reply("multi_select", &question.message)? {
+            Reply::MultiSelect(indices) => {
+                for &index in &indices {
+                    choice(&question.message, question.items.len(), index)?;
+                }
+                Ok(indices)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn spinner(&mut self, event: SpinnerEvent, message: &str) {
+        let line = match event {
+            SpinnerEvent::Start | SpinnerEvent::Message => format!("spinner: {message}"),
+            SpinnerEvent::Stop => format!("spinner done: {message}"),
+            SpinnerEvent::Error => format!("spinner failed: {message}"),
+        };
+        self.lock().push(line);
+    }
+}
+
+fn choice(message: &str, len: usize, index: usize) -> Result<(), Error> {
+    if index < len {
+        return Ok(());
+    }
+
+    Err(Error::new(GeenieError::invalid_answer(
+        message,
+        format!("choice {index} is out of range, there are {len} options"),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use spurgt::{confirm, input, select, Spinner, Spurgt};
+
+    use super::*;
+    use crate::{Context, File};
+
+    struct Greeting;
+
+    impl<C> Item<TestEnv, C> for Greeting {
+        fn process<'a>(
+            self,
+            mut ctx: Context<'a, TestEnv, C>,
+            env: &'a mut Spurgt<TestEnv>,
+        ) -> impl std::future::Future<Output = Result<(), GeenieError>> + 'a {
+            async move {
+                let name = env.ask(input("Name?").default("world")).await?;
+                let kind = env
+                    .ask(
+                        select("Kind?")
+                            .item("lib", "Library", "")
+                            .item("bin", "Binary", ""),
+                    )
+                    .await?;
+                if env.ask(confirm("Add readme?")).await? {
+                    ctx.file(File::new("README.md", format!("{name} ({kind})")))?;
+                }
+
+                let mut spinner = Spinner::new(env);
+                spinner.start("working");
+                spinner.stop("done");
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn answers_questions_in_order_and_logs_them() {
+        let env = TestEnv::new()
+            .expect_input("Name?", "")
+            .expect_select("Kind?", 1)
+            .expect_confirm("Add readme?", true);
+
+        let target = block_on(env.render(Greeting, &mut ())).unwrap();
+
+        assert_eq!(target.get("README.md").unwrap(), b"world (bin)");
+        assert_eq!(
+            env.log(),
+            vec![
+                "Name?",
+                "Kind?",
+                "Add readme?",
+                "spinner: working",
+                "spinner done: done"
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unexpected_and_mismatched_questions() {
+        let err = block_on(TestEnv::new().run_item(Greeting, &mut ()))
+            .map(drop)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "backend: unexpected input question: Name?");
+
+        let env = TestEnv::new().expect_confirm("Name?", true);
+        let err = block_on(env.run_item(Greeting, &mut ()))
+            .map(drop)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "backend: expected confirm question `Name?` but got input question `Name?`"
+        );
+
+        let env = TestEnv::new()
+            .expect_input("Name?", "app")
+            .expect_select("Kind?", 2);
+        assert!(matches!(
+            block_on(env.run_item(Greeting, &mut ())),
+            Err(GeenieError::InvalidAnswer { .. })
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "scripted questions were never asked: Later?")]
+    fn panics_when_replies_are_left_over() {
+        let env = TestEnv::new().expect_input("Later?", "x");
+        drop(env.clone());
+        drop(env);
+    }
+}
@@ -0,0 +1,619 @@
+use relative_path::{RelativePath, RelativePathBuf};
+use spurgt::Spurgt;
+use toml_edit::{Array, DocumentMut, InlineTable, Item, Table, Value};
+
+use crate::{Context, File, GeenieError, Item as GeenieItem};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    name: String,
+    table: String,
+    version: Option<String>,
+    path: Option<String>,
+    features: Vec<String>,
+    optional: Option<bool>,
+    default_features: Option<bool>,
+    workspace: bool,
+}
+
+impl Dependency {
+    pub fn new(name: impl ToString) -> Dependency {
+        Dependency {
+            name: name.to_string(),
+            table: String::from("dependencies"),
+            version: None,
+            path: None,
+            features: Vec::new(),
+            optional: None,
+            default_features: None,
+            workspace: false,
+        }
+    }
+
+    pub fn version(mut self, version: impl ToString) -> Self {
+        self.version = Some(version.to_string());
+        self
+    }
+
+    pub fn path(mut self, path: impl ToString) -> Self {
+        self.path = Some(path.to_string());
+        self
+    }
+
+    pub fn feature(mut self, feature: impl ToString) -> Self {
+        self.features.push(feature.to_string());
+        self
+    }
+
+    pub fn features<I>(mut self, features: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: ToString,
+    {
+        self.features
+            .extend(features.into_iter().map(|feature| feature.to_string()));
+        self
+    }
+
+    /// Mark the dependency optional, or drop an existing `optional` key.
+    pub fn optional(mut self, optional: bool) -> Self {
+        self.optional = Some(optional);
+        self
+    }
+
+    /// Disable default features, or drop an existing `default-features` key.
+    pub fn default_features(mut self, enabled: bool) -> Self {
+        self.default_features = Some(enabled);
+        self
+    }
+
+    pub fn workspace(mut self) -> Self {
+        self.workspace = true;
+        self
+    }
+
+    pub fn table(mut self, table: impl ToString) -> Self {
+        self.table = table.to_string();
+        self
+    }
+
+    pub fn dev(self) -> Self {
+        self.table("dev-dependencies")
+    }
+
+    pub fn build(self) -> Self {
+        self.table("build-dependencies")
+    }
+
+    fn is_simple(&self) -> bool {
+        self.path.is_none()
+            && self.features.is_empty()
+            && self.optional != Some(true)
+            && self.default_features != Some(false)
+            && !self.workspace
+    }
+
+    fn fields(&self) -> Vec<(&'static str, Value)> {
+        let mut fields = Vec::new();
+        if self.workspace {
+            fields.push(("workspace", Value::from(true)));
+        }
+        if let Some(version) = &self.version {
+            fields.push(("version", Value::from(version.as_str())));
+        }
+        if let Some(path) = &self.path {
+            fields.push(("path", Value::from(path.as_str())));
+        }
+        if self.default_features == Some(false) {
+            fields.push(("default-features", Value::from(false)));
+        }
+        if self.optional == Some(true) {
+            fields.push(("optional", Value::from(true)));
+        }
+        fields
+    }
+
+    /// Keys reset to cargo's default by removing them.
+    fn removed(&self) -> Vec<&'static str> {
+        let mut removed = Vec::new();
+        if self.default_features == Some(true) {
+            removed.extend(["default-features", "default_features"]);
+        }
+        if self.optional == Some(false) {
+            removed.push("optional");
+        }
+        removed
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Edit {
+    Table(String),
+    Set(String, Value),
+    Append(String, Value),
+    Remove(String),
+    Dependency(Dependency),
+}
+
+#[derive(Debug, Clone)]
+pub struct TomlEdit {
+    path: RelativePathBuf,
+    edits: Vec<Edit>,
+}
+
+impl TomlEdit {
+    pub fn new(path: impl Into<RelativePathBuf>) -> TomlEdit {
+        TomlEdit {
+            path: path.into(),
+            edits: Vec::new(),
+        }
+    }
+
+    pub fn table(mut self, key: impl ToString) -> Self {
+        self.edits.push(Edit::Table(key.to_string()));
+        self
+    }
+
+    pub fn set(mut self, key: impl ToString, value: impl Into<Value>) -> Self {
+        self.edits.push(Edit::Set(key.to_string(), value.into()));
+        self
+    }
+
+    pub fn append(mut self, key: impl ToString, value: impl Into<Value>) -> Self {
+        self.edits.push(Edit::Append(key.to_string(), value.into()));
+        self
+    }
+
+    pub fn remove(mut self, key: impl ToString) -> Self {
+        self.edits.push(Edit::Remove(key.to_string()));
+        self
+    }
+
+    pub fn dependency(mut self, dependency: Dependency) -> Self {
+        self.edits.push(Edit::Dependency(dependency));
+        self
+    }
+
+    pub fn apply(&self, existing: Option<&[u8]>) -> Result<File, GeenieError> {
+        let source = existing
+            .map(|content| String::from_utf8_lossy(content).into_owned())
+            .unwrap_or_default();
+
+        let mut document = source
+            .parse::<DocumentMut>()
+            .map_err(|err| GeenieError::backend(format!("could not parse {}: {err}", self.path)))?;
+
+        for edit in &self.edits {
+            match edit {
+                Edit::Table(key) => {
+                    let item = descend(document.as_item_mut(), &keys(key)?, key)?;
+                    match item {
+                        Item::Table(table) => table.set_implicit(false),
+                        Item::Value(Value::InlineTable(_)) => {}
+                        _ => return Err(not_table(key)),
+                    }
+                }
+                Edit::Set(key, value) => {
+                    let (parent, name) = split(key)?;
+                    let table = descend(document.as_item_mut(), &parent, key)?
+                        .as_table_like_mut()
+                        .ok_or_else(|| not_table(key))?;
+                    set(table, &name, value.clone());
+                }
+                Edit::Append(key, value) => {
+                    let item = descend(document.as_item_mut(), &keys(key)?, key)?;
+                    if is_empty_table(item) {
+                        *item = Item::Value(Value::Array(Array::new()));
+                    }
+                    let array = item
+                        .as_array_mut()
+                        .ok_or_else(|| GeenieError::backend(format!("{key} is not an array")))?;
+                    append(array, value.clone());
+                }
+                Edit::Remove(key) => {
+                    let (parent, name) = split(key)?;
+                    if let Some(table) = lookup(document.as_item_mut(), &parent)
+                        .and_then(|item| item.as_table_like_mut())
+                    {
+                        table.remove(&name);
+                    }
+                }
+                Edit::Dependency(dependency) => {
+                    let path = keys(&dependency.table)?;
+                    let table = descend(document.as_item_mut(), &path, &dependency.table)?
+                        .as_table_like_mut()
+                        .ok_or_else(|| not_table(&dependency.table))?;
+                    add_dependency(table, dependency);
+                }
+            }
+        }
+
+        Ok(File::new(self.path.clone(), document.to_string()))
+    }
+}
+
+impl<E, C> GeenieItem<E, C> for TomlEdit {
+    fn process<'a>(
+        self,
+        mut ctx: Context<'a, E, C>,
+        _env: &'a mut Spurgt<E>,
+    ) -> impl std::future::Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            let existing = ctx.require_existing(&self.path).await?;
+            let file = self.apply(Some(&existing.content))?;
+            ctx.file(File {
+                content: file.content,
+                ..existing
+            })?;
+            Ok(())
+        }
+    }
+}
+
+pub fn toml(path: impl Into<RelativePathBuf>) -> TomlEdit {
+    TomlEdit::new(path)
+}
+
+pub(crate) fn merge_documents(
+    path: &RelativePath,
+    existing: &[u8],
+    incoming: &[u8],
+) -> Result<Vec<u8>, GeenieError> {
+    let parse = |content: &[u8]| {
+        String::from_utf8_lossy(content)
+            .parse::<DocumentMut>()
+            .map_err(|err| GeenieError::backend(format!("could not parse {path}: {err}")))
+    };
+
+    let mut document = parse(existing)?;
+    merge_tables(document.as_table_mut(), parse(incoming)?.as_table());
+    Ok(document.to_string().into_bytes())
+}
+
+fn merge_tables(target: &mut dyn toml_edit::TableLike, other: &dyn toml_edit::TableLike) {
+    for (key, item) in other.iter() {
+        if let (Some(existing), Some(other)) = (
+            target.get_mut(key).and_then(Item::as_table_like_mut),
+            item.as_table_like(),
+        ) {
+            merge_tables(existing, other);
+            continue;
+        }
+
+        target.insert(key, item.clone());
+    }
+}
+
+fn add_dependency(table: &mut dyn toml_edit::TableLike, dependency: &Dependency) {
+    let name = dependency.name.as_str();
+
+    if dependency.is_simple() {
+        if let Some(version) = &dependency.version {
+            match table.get_mut(name) {
+                Some(item) if item.as_table_like().is_some() => {}
+                _ => {
+                    set(table, name, Value::from(version.as_str()));
+                    return;
+                }
+            }
+        }
+    }
+
+    let item = table
+        .entry(name)
+        .or_insert(Item::Value(Value::InlineTable(InlineTable::new())));
+
+    if item.as_table_like().is_none() {
+        let mut inline = InlineTable::new();
+        if let Some(version) = item.as_str() {
+            inline.insert("version", Value::from(version));
+        }
+        let mut value = Value::InlineTable(inline);
+        if let Some(existing) = item.as_value() {
+            *value.decor_mut() = existing.decor().clone();
+        }
+        *item = Item::Value(value);
+    }
+
+    let fields = item
+        .as_table_like_mut()
+        .expect("dependency was converted to a table");
+
+    for (key, value) in dependency.fields() {
+        set(fields, key, value);
+    }
+
+    for key in dependency.removed() {
+        fields.remove(key);
+    }
+
+    if !dependency.features.is_empty() {
+        let features = fields
+            .entry("features")
+            .or_insert(Item::Value(Value::Array(Array::new())));
+        if let Some(features) = features.as_array_mut() {
+            for feature in &dependency.features {
+                append(features, Value::from(feature.as_str()));
+            }
+        }
+    }
+}
+
+fn set(table: &mut dyn toml_edit::TableLike, key: &str, mut value: Value) {
+    if let Some(existing) = table.get(key).and_then(Item::as_value) {
+        *value.decor_mut() = existing.decor().clone();
+    }
+    table.insert(key, Item::Value(value));
+}
+
+fn append(array: &mut Array, value: Value) {
+    if array.iter().any(|existing| same(existing, &value)) {
+        return;
+    }
+
+    let prefix = array
+        .iter()
+        .last()
+        .and_then(|last| last.decor().prefix())
+        .and_then(|prefix| prefix.as_str())
+        .and_then(|prefix| prefix.rfind('\n').map(|index| prefix[index..].to_string()));
+
+    match prefix {
+        Some(prefix) => array.push_formatted(value.decorated(prefix, "")),
+        None => array.push(value),
+    }
+}
+
+fn same(a: &Value, b: &Value) -> bool {
+    let a = a.clone().decorated("", "");
+    let b = b.clone().decorated("", "");
+    a.to_string() == b.to_string()
+}
+
+fn descend<'a>(
+    mut item: &'a mut Item,
+    path: &[String],
+    key: &str,
+) -> Result<&'a mut Item, GeenieError> {
+    for name in path {
+        let inline = item.is_inline_table();
+        let table = item.as_table_like_mut().ok_or_else(|| not_table(key))?;
+        if !table.contains_key(name) {
+            let child = if inline {
+                Item::Value(Value::InlineTable(InlineTable::new()))
+            } else {
+                let mut table = Table::new();
+                table.set_implicit(true);
+                Item::Table(table)
+            };
+            table.insert(name, child);
+        }
+        item = table.get_mut(name).expect("key was just inserted");
+    }
+    Ok(item)
+}
+
+fn lookup<'a>(mut item: &'a mut Item, path: &[String]) -> Option<&'a mut Item> {
+    for name in path {
+        item = item.as_table_like_mut()?.get_mut(name)?;
+    }
+    Some(item)
+}
+
+fn is_empty_table(item: &Item) -> bool {
+    item.as_table_like().is_some_and(|table| table.is_empty())
+}
+
+/// The names of a dotted key, which may quote names with dots in them like
+/// `package.metadata."docs.rs"`. An empty key is the document root.
+fn keys(key: &str) -> Result<Vec<String>, GeenieError> {
+    if key.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    toml_edit::Key::parse(key)
+        .map(|keys| keys.iter().map(|key| key.get().to_string()).collect())
+        .map_err(|err| GeenieError::backend(format!("invalid toml key {key}: {err}")))
+}
+
+fn split(key: &str) -> Result<(Vec<String>, String), GeenieError> {
+    let mut path = keys(key)?;
+    let name = path
+        .pop()
+        .ok_or_else(|| GeenieError::backend(format!("invalid toml key {key:?}")))?;
+    Ok((path, name))
+}
+
+fn not_table(key: &str) -> GeenieError {
+    GeenieError::backend(format!("{key} is not a table"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"[package]
+name = "app" # the crate
+version = "0.1.0"
+
+[dependencies]
+serde = "1"
+tokio = { version = "1", features = [
+    "rt",
+] }
+
+[features]
+default = []
+"#;
+
+    fn edit(edit: TomlEdit) -> String {
+        String::from_utf8(edit.apply(Some(MANIFEST.as_bytes())).unwrap().content).unwrap()
+    }
+
+    #[test]
+    fn sets_appends_and_removes_keys() {
+        let output = edit(
+            toml("Cargo.toml")
+                .set("package.name", "demo")
+                .set("package.metadata.docs.all", true)
+                .append("features.default", "std")
+                .append("features.default", "std")
+                .append("features.full", "tokio")
+                .remove("dependencies.serde")
+                .remove("missing.key"),
+        );
+
+        assert_eq!(
+            output,
+            r#"[package]
+name = "demo" # the crate
+version = "0.1.0"
+
+[package.metadata.docs]
+all = true
+
+[dependencies]
+tokio = { version = "1", features = [
+    "rt",
+] }
+
+[features]
+default = ["std"]
+full = ["tokio"]
+"#
+        );
+    }
+
+    #[test]
+    fn adds_and_updates_dependencies() {
+        let output = edit(
+            toml("Cargo.toml")
+                .dependency(
+                    Dependency::new("serde")
+                        .version("1.0.200")
+                        .feature("derive"),
+                )
+                .dependency(Dependency::new("tokio").feature("macros").feature("rt"))
+                .dependency(Dependency::new("anyhow").version("1"))
+                .dependency(Dependency::new("insta").version("1").dev())
+                .dependency(Dependency::new("core").workspace().optional(true)),
+        );
+
+        assert_eq!(
+            output,
+            r#"[package]
+name = "app" # the crate
+version = "0.1.0"
+
+[dependencies]
+serde = { version = "1.0.200", features = ["derive"] }
+tokio = { version = "1", features = [
+    "rt",
+    "macros",
+] }
+anyhow = "1"
+core = { workspace = true, optional = true }
+
+[features]
+default = []
+
+[dev-dependencies]
+insta = "1"
+"#
+        );
+    }
+
+    #[test]
+    fn addresses_quoted_keys() {
+        let source = "[target.'cfg(windows)'.dependencies]\nwinapi = \"0.3\"\n";
+        let output = toml("Cargo.toml")
+            .set(r#"package.metadata."docs.rs".all-features"#, true)
+            .dependency(
+                Dependency::new("windows")
+                    .version("0.58")
+                    .table("target.'cfg(windows)'.dependencies"),
+            )
+            .remove(r#"target."cfg(windows)".dependencies.winapi"#)
+            .apply(Some(source.as_bytes()))
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output.content).unwrap(),
+            "[target.'cfg(windows)'.dependencies]\nwindows = \"0.58\"\n\n[package.metadata.\"docs.rs\"]\nall-features = true\n"
+        );
+        assert!(toml("Cargo.toml")
+            .set("package.'name", "x")
+            .apply(Some(b""))
+            .is_err());
+        assert!(toml("Cargo.toml").remove("").apply(Some(b"")).is_err());
+    }
+
+    #[test]
+    fn resets_dependency_fields() {
+        let output = edit(
+            toml("Cargo.toml")
+                .dependency(
+                    Dependency::new("tokio")
+                        .optional(true)
+                        .default_features(false),
+                )
+                .dependency(
+                    Dependency::new("tokio")
+                        .optional(false)
+                        .default_features(true),
+                )
+                .dependency(Dependency::new("serde").version("2").optional(false)),
+        );
+
+        assert!(
+            output.contains(
+                "serde = \"2\"\ntokio = { version = \"1\", features = [\n    \"rt\",\n] }\n"
+            ),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn rejects_non_tables_and_invalid_documents() {
+        assert!(toml("Cargo.toml")
+            .set("package.name.first", "x")
+            .apply(Some(MANIFEST.as_bytes()))
+            .is_err());
+        assert!(toml("Cargo.toml")
+            .append("package.name", "x")
+            .apply(Some(MANIFEST.as_bytes()))
+            .is_err());
+        assert!(toml("Cargo.toml").apply(Some(b"[package")).is_err());
+    }
+
+    #[test]
+    fn merges_documents() {
+        let merged = merge_documents(
+            RelativePath::new("Cargo.toml"),
+            MANIFEST.as_bytes(),
+            b"[package]\nedition = \"2021\"\n\n[features]\nfull = []\n",
+        )
+        .unwrap();
+
+        let merged = String::from_utf8(merged).unwrap();
+        assert!(merged
+            .contains("name = \"app\" # the crate\nversion = \"0.1.0\"\nedition = \"2021\"\n"));
+        assert!(merged.contains("default = []\nfull = []\n"));
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn requires_an_existing_file() {
+        use futures::executor::block_on;
+
+        use crate::testing::TestEnv;
+
+        let err =
+            block_on(TestEnv::new().run_item(toml("Cargo.toml").set("package.name", "x"), &mut ()))
+                .map(drop)
+                .unwrap_err();
+
+        assert_eq!(err.to_string(), "cannot edit missing file: Cargo.toml");
+        assert_eq!(toml("Cargo.toml").apply(Some(b"")).unwrap().conflict, None);
+    }
+}
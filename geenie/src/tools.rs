@@ -0,0 +1,394 @@
+use core::{fmt, str::FromStr};
+use std::path::{Path, PathBuf};
+
+use spurgt::{Asger, Spurgt};
+
+use crate::{
+    error::{MissingTool, ToolProblem},
+    process, Context, GeenieError, Item,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub fn new(major: u64, minor: u64, patch: u64) -> Version {
+        Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Find a version in the output of a tool.
+    ///
+    /// The first dotted number such as `1.2.3` wins, so `python3 3.11` gives
+    /// `3.11.0`. Without one, a number standing on its own, optionally after
+    /// a `v`, is taken as the major version.
+    pub fn parse(text: &str) -> Option<Version> {
+        text.split(|c: char| !c.is_ascii_digit() && c != '.')
+            .map(|run| run.trim_matches('.'))
+            .filter(|run| run.contains('.'))
+            .find_map(Version::from_parts)
+            .or_else(|| {
+                text.split_whitespace()
+                    .map(|word| word.trim_matches(|c: char| !c.is_ascii_alphanumeric()))
+                    .map(|word| word.strip_prefix('v').unwrap_or(word))
+                    .find_map(Version::from_parts)
+            })
+    }
+
+    fn from_parts(text: &str) -> Option<Version> {
+        let parts = text
+            .split('.')
+            .map(|part| part.parse::<u64>().ok())
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Version {
+            major: *parts.first()?,
+            minor: parts.get(1).copied().unwrap_or_default(),
+            patch: parts.get(2).copied().unwrap_or_default(),
+        })
+    }
+}
+
+impl FromStr for Version {
+    type Err = GeenieError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Version::parse(s).ok_or_else(|| GeenieError::backend(format!("invalid version: {s}")))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+pub fn which(program: impl AsRef<Path>) -> Option<PathBuf> {
+    let program = program.as_ref();
+    if program.components().count() > 1 {
+        return is_executable(program).then(|| program.to_path_buf());
+    }
+
+    let paths = std::env::var_os("PATH")?;
+    let extensions = executable_extensions();
+
+    std::env::split_paths(&paths).find_map(|dir| {
+        extensions.iter().find_map(|ext| {
+            let mut candidate = dir.join(program);
+            if !ext.is_empty() {
+                candidate.as_mut_os_string().push(ext);
+            }
+            is_executable(&candidate).then_some(candidate)
+        })
+    })
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+    }
+
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+pub fn suggest(program: &str) -> Option<String> {
+    let paths = std::env::var_os("PATH")?;
+
+    closest(
+        program,
+        std::env::split_paths(&paths)
+            .filter_map(|dir| std::fs::read_dir(dir).ok())
+            .flatten()
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok()),
+    )
+}
+
+fn closest(program: &str, names: impl Iterator<Item = String>) -> Option<String> {
+    names
+        .map(|name| (distance(program, &name), name))
+        .filter(|(distance, _)| *distance > 0 && *distance <= 2)
+        .min()
+        .map(|(_, name)| name)
+}
+
+fn executable_extensions() -> Vec<String> {
+    if cfg!(windows) {
+        let mut extensions = std::env::var("PATHEXT")
+            .unwrap_or_else(|_| String::from(".COM;.EXE;.BAT;.CMD"))
+            .split(';')
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        extensions.insert(0, String::new());
+        extensions
+    } else {
+        vec![String::new()]
+    }
+}
+
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                prev
+            } else {
+                1 + prev.min(row[j]).min(current)
+            };
+            prev = current;
+        }
+    }
+
+    row[b.len()]
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tool {
+    name: String,
+    min_version: Option<Version>,
+    version_arg: String,
+}
+
+impl Tool {
+    pub fn new(name: impl ToString) -> Tool {
+        Tool {
+            name: name.to_string(),
+            min_version: None,
+            version_arg: String::from("--version"),
+        }
+    }
+
+    pub fn min_version(mut self, version: Version) -> Self {
+        self.min_version = Some(version);
+        self
+    }
+
+    pub fn version_arg(mut self, arg: impl ToString) -> Self {
+        self.version_arg = arg.to_string();
+        self
+    }
+
+    pub async fn check(&self) -> Result<(), MissingTool> {
+        let missing = |problem| MissingTool {
+            name: self.name.clone(),
+            problem,
+        };
+
+        if which(&self.name).is_none() {
+            return Err(missing(ToolProblem::NotFound {
+                suggestion: suggest(&self.name),
+            }));
+        }
+
+        let Some(required) = self.min_version else {
+            return Ok(());
+        };
+
+        let found = process(&self.name)
+            .arg(&self.version_arg)
+            .capture()
+            .await
+            .ok()
+            .and_then(|output| {
+                Version::parse(&output.stdout).or_else(|| Version::parse(&output.stderr))
+            });
+
+        match found {
+            Some(found) if found >= required => Ok(()),
+            Some(found) => Err(missing(ToolProblem::Outdated {
+                found: found.to_string(),
+                required: required.to_string(),
+            })),
+            None => Err(missing(ToolProblem::UnknownVersion {
+                required: required.to_string(),
+            })),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Prerequisites {
+    tools: Vec<Tool>,
+}
+
+impl Prerequisites {
+    pub fn new() -> Prerequisites {
+        Prerequisites::default()
+    }
+
+    pub fn tool(mut self, tool: impl Into<Tool>) -> Self {
+        self.tools.push(tool.into());
+        self
+    }
+
+    pub fn require(self, name: impl ToString) -> Self {
+        self.tool(Tool::new(name))
+    }
+
+    pub fn require_version(self, name: impl ToString, version: Version) -> Self {
+        self.tool(Tool::new(name).min_version(version))
+    }
+
+    pub async fn check(&self) -> Result<(), GeenieError> {
+        let mut missing = Vec::new();
+        for tool in &self.tools {
+            if let Err(err) = tool.check().await {
+                missing.push(err);
+            }
+        }
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(GeenieError::MissingTools { tools: missing })
+        }
+    }
+}
+
+impl From<&str> for Tool {
+    fn from(name: &str) -> Self {
+        Tool::new(name)
+    }
+}
+
+impl From<String> for Tool {
+    fn from(name: String) -> Self {
+        Tool::new(name)
+    }
+}
+
+impl<E: Asger, C> Item<E, C> for Prerequisites {
+    fn process<'a>(
+        self,
+        _ctx: Context<'a, E, C>,
+        env: &'a mut Spurgt<E>,
+    ) -> impl std::future::Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            let Err(err) = self.check().await else {
+                return Ok(());
+            };
+
+            if let GeenieError::MissingTools { tools } = &err {
+                let mut message = String::from("Missing required tools:");
+                for tool in tools {
+                    message.push_str(&format!("\n  {tool}"));
+                }
+                env.info(&message).await.map_err(GeenieError::backend)?;
+            }
+
+            Err(err)
+        }
+    }
+}
+
+pub fn prerequisites() -> Prerequisites {
+    Prerequisites::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+
+    #[test]
+    fn parses_dotted_versions_first() {
+        let parse = |text| Version::parse(text);
+
+        assert_eq!(
+            parse("rustc 1.79.0 (129f3b996 2024-06-10)"),
+            Some(Version::new(1, 79, 0))
+        );
+        assert_eq!(parse("tool 2 (build 1.2.3)"), Some(Version::new(1, 2, 3)));
+        assert_eq!(parse("python3 version 3.11"), Some(Version::new(3, 11, 0)));
+        assert_eq!(
+            parse("go version go1.21.5 linux/amd64"),
+            Some(Version::new(1, 21, 5))
+        );
+        assert_eq!(parse("node v18."), Some(Version::new(18, 0, 0)));
+        assert_eq!(parse("cmake 3"), Some(Version::new(3, 0, 0)));
+        assert_eq!(parse("python3"), None);
+        assert!("unknown".parse::<Version>().is_err());
+    }
+
+    #[test]
+    fn suggests_close_names_only() {
+        let names = || ["cargo", "cat", "rustc"].map(String::from).into_iter();
+
+        assert_eq!(closest("carg", names()).as_deref(), Some("cargo"));
+        assert_eq!(closest("cart", names()).as_deref(), Some("cat"));
+        assert_eq!(closest("cat", names()), None);
+        assert_eq!(closest("python", names()), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn checks_executables_and_versions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("geenie-tools-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let tool = dir.join("tool");
+        let data = dir.join("data");
+        std::fs::write(&tool, "#!/bin/sh\necho 'tool 2 (build 1.2.3)'\n").unwrap();
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::write(&data, "").unwrap();
+
+        assert_eq!(which(&tool), Some(tool.clone()));
+        assert_eq!(which(&data), None);
+
+        let tool = tool.display().to_string();
+        let data = data.display().to_string();
+        let ok = block_on(
+            prerequisites()
+                .require_version(&tool, Version::new(1, 2, 0))
+                .check(),
+        );
+        let err = block_on(
+            prerequisites()
+                .require_version(&tool, Version::new(2, 0, 0))
+                .require(&data)
+                .check(),
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(ok.is_ok());
+        let Err(GeenieError::MissingTools { tools }) = err else {
+            panic!("expected missing tools");
+        };
+        assert_eq!(
+            tools,
+            vec![
+                MissingTool {
+                    name: tool,
+                    problem: ToolProblem::Outdated {
+                        found: String::from("1.2.3"),
+                        required: String::from("2.0.0"),
+                    },
+                },
+                MissingTool {
+                    name: data,
+                    problem: ToolProblem::NotFound { suggestion: None },
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,402 @@
+use std::{collections::BTreeSet, future::Future};
+
+use futures::lock::Mutex;
+use relative_path::{RelativePath, RelativePathBuf};
+
+use crate::{target::Target, GeenieError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Change {
+    Created(RelativePathBuf),
+    Replaced {
+        path: RelativePathBuf,
+        content: Vec<u8>,
+        mode: Option<u32>,
+    },
+    Linked {
+        path: RelativePathBuf,
+        target: String,
+    },
+}
+
+impl Change {
+    fn path(&self) -> &RelativePath {
+        match self {
+            Change::Created(path) | Change::Replaced { path, .. } | Change::Linked { path, .. } => {
+                path
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Journal {
+    seen: BTreeSet<RelativePathBuf>,
+    changes: Vec<Change>,
+}
+
+/// Journals every change made through it so they can be rolled back.
+///
+/// The first write, mode change, symlink or removal of a path saves what was
+/// there before: its content and mode, its link text, or the fact that it did
+/// not exist. Rolling back restores that state and removes created
+/// directories along with anything put in them later. Changes made behind the
+/// transaction's back to pre-existing files are not journaled, which is why
+/// `GeenieResult::commit` only runs commands after committing.
+#[must_use = "a transaction must be committed or rolled back"]
+#[derive(Debug)]
+pub struct Transaction<T> {
+    target: T,
+    journal: Mutex<Journal>,
+}
+
+impl<T: Target> Transaction<T> {
+    pub fn new(target: T) -> Transaction<T> {
+        Transaction {
+            target,
+            journal: Mutex::new(Journal::default()),
+        }
+    }
+
+    pub fn target(&self) -> &T {
+        &self.target
+    }
+
+    pub async fn changed(&self) -> Vec<RelativePathBuf> {
+        self.journal
+            .lock()
+            .await
+            .changes
+            .iter()
+            .map(|change| change.path().to_relative_path_buf())
+            .collect()
+    }
+
+    pub fn commit(self) -> T {
+        self.target
+    }
+
+    pub async fn rollback(self) -> Result<T, GeenieError> {
+        let journal = self.journal.into_inner();
+        let mut error = None;
+
+        for change in journal.changes.into_iter().rev() {
+            if let Err(err) = Self::restore(&self.target, &change).await {
+                error.get_or_insert(err);
+            }
+        }
+
+        match error {
+            Some(err) => Err(err),
+            None => Ok(self.target),
+        }
+    }
+
+    async fn restore(target: &T, change: &Change) -> Result<(), GeenieError> {
+        match change {
+            Change::Created(path) => target.remove_all(path).await,
+            Change::Replaced {
+                path,
+                content,
+                mode,
+            } => {
+                target.remove(path).await?;
+                match mode {
+                    Some(mode) => target.write_mode(path, content, *mode).await,
+                    None => target.write(path, content).await,
+                }
+            }
+            Change::Linked { path, target: link } => target.symlink(path, link).await,
+        }
+    }
+
+    async fn record(&self, path: &RelativePath) -> Result<(), GeenieError> {
+        let mut journal = self.journal.lock().await;
+
+        let mut ancestors = Vec::new();
+        let mut parent = path.parent();
+        while let Some(dir) = parent {
+            if !dir.as_str().is_empty() {
+                ancestors.push(dir);
+            }
+            parent = dir.parent();
+        }
+
+        for dir in ancestors.into_iter().rev() {
+            if journal.seen.insert(dir.to_relative_path_buf()) && !self.target.exists(dir).await? {
+                journal
+                    .changes
+                    .push(Change::Created(dir.to_relative_path_buf()));
+            }
+        }
+
+        if !journal.seen.insert(path.to_relative_path_buf()) {
+            return Ok(());
+        }
+
+        let change = if !self.target.exists(path).await? {
+            Some(Change::Created(path.to_relative_path_buf()))
+        } else if let Some(target) = self.target.read_link(path).await? {
+            Some(Change::Linked {
+                path: path.to_relative_path_buf(),
+                target,
+            })
+        } else {
+            match self.target.read(path).await {
+                Ok(Some(content)) => Some(Change::Replaced {
+                    path: path.to_relative_path_buf(),
+                    content,
+                    mode: self.target.read_mode(path).await?,
+                }),
+                // Directories have nothing to save.
+                Ok(None) => None,
+                Err(GeenieError::Io(err)) if err.kind() == std::io::ErrorKind::IsADirectory => None,
+                Err(err) => return Err(err),
+            }
+        };
+
+        journal.changes.extend(change);
+
+        Ok(())
+    }
+}
+
+impl<T: Target> Target for Transaction<T> {
+    fn read<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<Option<Vec<u8>>, GeenieError>> + 'a {
+        self.target.read(path)
+    }
+
+    fn write<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        content: &'a [u8],
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            self.record(path).await?;
+            self.target.write(path, content).await
+        }
+    }
+
+    fn create_dir<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            self.record(path).await?;
+            self.target.create_dir(path).await
+        }
+    }
+
+    fn create_dir_mode<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        mode: u32,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            self.record(path).await?;
+            self.target.create_dir_mode(path, mode).await
+        }
+    }
+
+    fn write_mode<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        content: &'a [u8],
+        mode: u32,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            self.record(path).await?;
+            self.target.write_mode(path, content, mode).await
+        }
+    }
+
+    fn set_mode<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        mode: u32,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            self.record(path).await?;
+            self.target.set_mode(path, mode).await
+        }
+    }
+
+    fn symlink<'a>(
+        &'a self,
+        path: &'a RelativePath,
+        target: &'a str,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            self.record(path).await?;
+            self.target.symlink(path, target).await
+        }
+    }
+
+    fn exists<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<bool, GeenieError>> + 'a {
+        self.target.exists(path)
+    }
+
+    fn remove<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<(), GeenieError>> + 'a {
+        async move {
+            self.record(path).await?;
+            self.target.remove(path).await
+        }
+    }
+
+    fn read_mode<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<Option<u32>, GeenieError>> + 'a {
+        self.target.read_mode(path)
+    }
+
+    fn read_link<'a>(
+        &'a self,
+        path: &'a RelativePath,
+    ) -> impl Future<Output = Result<Option<String>, GeenieError>> + 'a {
+        self.target.read_link(path)
+    }
+
+    fn root(&self) -> Option<&std::path::Path> {
+        self.target.root()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::MemoryTarget;
+
+    fn rolled_back(
+        target: MemoryTarget,
+        apply: impl AsyncFnOnce(&Transaction<MemoryTarget>) -> Result<(), GeenieError>,
+    ) -> MemoryTarget {
+        block_on(async {
+            let transaction = Transaction::new(target);
+            apply(&transaction).await.unwrap();
+            transaction.rollback().await.unwrap()
+        })
+    }
+
+    #[test]
+    fn restores_content_and_mode() {
+        let target = rolled_back(MemoryTarget::new().with_file("run.sh", "old"), async |tx| {
+            tx.write_mode("run.sh".as_ref(), b"new", 0o755).await?;
+            tx.write("new.txt".as_ref(), b"x").await
+        });
+
+        assert_eq!(target.get("run.sh").unwrap(), b"old");
+        assert_eq!(target.mode("run.sh"), Some(0o644));
+        assert!(target.get("new.txt").is_none());
+    }
+
+    #[test]
+    fn restores_mode_changes() {
+        let target = rolled_back(
+            MemoryTarget::new().with_file("run.sh", "echo"),
+            async |tx| tx.set_mode("run.sh".as_ref(), 0o755).await,
+        );
+
+        assert_eq!(target.mode("run.sh"), Some(0o644));
+    }
+
+    #[test]
+    fn restores_files_and_links_replaced_by_each_other() {
+        let existing = MemoryTarget::new().with_file("a.txt", "keep");
+        block_on(existing.symlink("link".as_ref(), "a.txt")).unwrap();
+
+        let target = rolled_back(existing, async |tx| {
+            tx.symlink("a.txt".as_ref(), "elsewhere").await?;
+            tx.write("link".as_ref(), b"file").await
+        });
+
+        assert_eq!(target.get("a.txt").unwrap(), b"keep");
+        assert_eq!(target.link("link").as_deref(), Some("a.txt"));
+    }
+
+    #[test]
+    fn removes_created_directories_with_their_contents() {
+        let target = rolled_back(
+            MemoryTarget::new().with_file("README.md", "hi"),
+            async |tx| {
+                tx.write("app/src/main.rs".as_ref(), b"fn main() {}")
+                    .await?;
+                // Written behind the transaction's back, like a command would.
+                tx.target()
+                    .write("app/target/debug/app".as_ref(), b"bin")
+                    .await
+            },
+        );
+
+        assert_eq!(
+            target.entries().into_keys().collect::<Vec<_>>(),
+            vec![RelativePathBuf::from("README.md")]
+        );
+    }
+
+    #[test]
+    fn records_each_path_once() {
+        block_on(async {
+            let transaction = Transaction::new(MemoryTarget::new().with_file("a.txt", "1"));
+            transaction.write("a.txt".as_ref(), b"2").await.unwrap();
+            transaction.write("a.txt".as_ref(), b"3").await.unwrap();
+            transaction.create_dir("dir".as_ref()).await.unwrap();
+
+            assert_eq!(
+                transaction.changed().await,
+                vec![RelativePathBuf::from("a.txt"), RelativePathBuf::from("dir")]
+            );
+
+            let target = transaction.rollback().await.unwrap();
+            assert_eq!(target.get("a.txt").unwrap(), b"1");
+            assert!(!target.is_dir("dir"));
+        });
+    }
+
+    #[cfg(all(feature = "fs", unix))]
+    #[test]
+    fn restores_the_filesystem() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root =
+            std::env::temp_dir().join(format!("geenie-transaction-fs-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("run.sh"), "old").unwrap();
+        std::fs::set_permissions(root.join("run.sh"), std::fs::Permissions::from_mode(0o600))
+            .unwrap();
+
+        block_on(async {
+            let transaction = Transaction::new(crate::FsTarget::new(&root));
+            transaction
+                .symlink("run.sh".as_ref(), "elsewhere")
+                .await
+                .unwrap();
+            transaction
+                .write("out/file.txt".as_ref(), b"new")
+                .await
+                .unwrap();
+            std::fs::write(root.join("out/extra.txt"), "command output").unwrap();
+            transaction.rollback().await.unwrap();
+        });
+
+        let meta = std::fs::symlink_metadata(root.join("run.sh")).unwrap();
+        assert!(meta.is_file());
+        assert_eq!(meta.permissions().mode() & 0o7777, 0o600);
+        assert_eq!(std::fs::read(root.join("run.sh")).unwrap(), b"old");
+        assert!(!root.join("out").exists());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,518 @@
+use std::{borrow::Cow, collections::BTreeMap, path::Path};
+
+use relative_path::{RelativePath, RelativePathBuf};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    answers::Session,
+    conflict::{WriteOutcome, WriteReport},
+    diff::is_binary,
+    target::{FsTarget, Target},
+    File, FileKind, FileList, GeenieError,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    pub generator: String,
+    pub version: String,
+    #[serde(default)]
+    pub answers: Session,
+    #[serde(default)]
+    pub files: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub base: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub links: BTreeMap<String, String>,
+}
+
+impl Metadata {
+    pub const FILE: &'static str = ".geenie";
+
+    pub fn new(generator: impl ToString, version: impl ToString, session: Session) -> Metadata {
+        Metadata {
+            generator: generator.to_string(),
+            version: version.to_string(),
+            answers: session,
+            files: BTreeMap::default(),
+            base: BTreeMap::default(),
+            links: BTreeMap::default(),
+        }
+    }
+
+    pub fn track(&mut self, files: &FileList) -> &mut Self {
+        for file in files {
+            self.record(file);
+        }
+        self
+    }
+
+    fn record(&mut self, file: &File) {
+        match &file.kind {
+            FileKind::File => {}
+            FileKind::Symlink(link) => {
+                self.links.insert(file.path.to_string(), link.clone());
+                return;
+            }
+            FileKind::Dir => return,
+        }
+
+        self.files
+            .insert(file.path.to_string(), hash(&file.content));
+
+        match core::str::from_utf8(&file.content) {
+            Ok(content) if !is_binary(&file.content) => {
+                self.base.insert(file.path.to_string(), content.to_string());
+            }
+            _ => {
+                self.base.remove(file.path.as_str());
+            }
+        }
+    }
+
+    pub fn is_pristine(&self, path: &RelativePath, content: &[u8]) -> bool {
+        self.files
+            .get(path.as_str())
+            .is_some_and(|recorded| *recorded == hash(content))
+    }
+
+    pub fn to_json(&self) -> Result<String, GeenieError> {
+        serde_json::to_string_pretty(self).map_err(GeenieError::backend)
+    }
+
+    pub fn from_json(source: &str) -> Result<Metadata, GeenieError> {
+        serde_json::from_str(source).map_err(GeenieError::backend)
+    }
+
+    pub async fn load(path: impl AsRef<Path>) -> Result<Metadata, GeenieError> {
+        Metadata::load_from(&FsTarget::new(path.as_ref())).await
+    }
+
+    pub async fn load_from<T: Target>(target: &T) -> Result<Metadata, GeenieError> {
+        let source = target
+            .read(RelativePath::new(Metadata::FILE))
+            .await?
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))?;
+        Metadata::from_json(&String::from_utf8(source).map_err(GeenieError::backend)?)
+    }
+
+    pub async fn write_to(&self, path: impl AsRef<Path>) -> Result<(), GeenieError> {
+        self.write_into(&FsTarget::new(path.as_ref())).await
+    }
+
+    pub async fn write_into<T: Target>(&self, target: &T) -> Result<(), GeenieError> {
+        target
+            .write(
+                RelativePath::new(Metadata::FILE),
+                self.to_json()?.as_bytes(),
+            )
+            .await
+    }
+}
+
+pub fn hash(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+pub struct Update {
+    metadata: Metadata,
+    base: Option<FileList>,
+}
+
+impl Update {
+    pub fn new(metadata: Metadata) -> Update {
+        Update {
+            metadata,
+            base: None,
+        }
+    }
+
+    pub fn base(mut self, files: FileList) -> Self {
+        self.base = Some(files);
+        self
+    }
+
+    pub async fn apply(
+        self,
+        path: impl AsRef<Path>,
+        version: impl ToString,
+        files: &FileList,
+    ) -> Result<WriteReport, GeenieError> {
+        self.apply_into(&FsTarget::new(path.as_ref()), version, files)
+            .await
+    }
+
+    pub async fn apply_into<T: Target>(
+        self,
+        target: &T,
+        version: impl ToString,
+        files: &FileList,
+    ) -> Result<WriteReport, GeenieError> {
+        let mut report = WriteReport::default();
+        let mut metadata = Metadata {
+            version: version.to_string(),
+            files: BTreeMap::default(),
+            base: BTreeMap::default(),
+            links: BTreeMap::default(),
+            ..self.metadata.clone()
+        };
+
+        for file in files {
+            let outcome = match &file.kind {
+                FileKind::Dir if target.exists(&file.path).await? => {
+                    sync_mode(target, file).await?;
+                    WriteOutcome::Unchanged
+                }
+                FileKind::Dir => {
+                    match file.mode {
+                        Some(mode) => target.create_dir_mode(&file.path, mode).await?,
+                        None => target.create_dir(&file.path).await?,
+                    }
+                    WriteOutcome::Created
+                }
+                FileKind::Symlink(link) => self.link(target, file, link).await?,
+                FileKind::File => {
+                    let (write, outcome) = self.resolve(target, file).await?;
+
+                    match write {
+                        Some((dest, content)) => match file.mode {
+                            Some(mode) => target.write_mode(&dest, &content, mode).await?,
+                            None => target.write(&dest, &content).await?,
+                        },
+                        None if outcome == WriteOutcome::Unchanged => {
+                            sync_mode(target, file).await?
+                        }
+                        None => {}
+                    }
+
+                    outcome
+                }
+            };
+
+            metadata.record(file);
+            report.push(file.path.clone(), outcome);
+        }
+
+        for (dropped, recorded) in &self.metadata.files {
+            let dropped = RelativePath::new(dropped);
+            if metadata.files.contains_key(dropped.as_str()) {
+                continue;
+            }
+
+            let Some(current) = target.read(dropped).await? else {
+                continue;
+            };
+
+            if hash(&current) == *recorded {
+                target.remove(dropped).await?;
+                report.push(dropped.to_relative_path_buf(), WriteOutcome::Removed);
+            } else {
+                report.push(dropped.to_relative_path_buf(), WriteOutcome::Skipped);
+            }
+        }
+
+        for (dropped, recorded) in &self.metadata.links {
+            let dropped = RelativePath::new(dropped);
+            if metadata.links.contains_key(dropped.as_str()) || !target.exists(dropped).await? {
+                continue;
+            }
+
+            if target.read_link(dropped).await?.as_ref() == Some(recorded) {
+                target.remove(dropped).await?;
+                report.push(dropped.to_relative_path_buf(), WriteOutcome::Removed);
+            } else {
+                report.push(dropped.to_relative_path_buf(), WriteOutcome::Skipped);
+            }
+        }
+
+        metadata.write_into(target).await?;
+
+        Ok(report)
+    }
+
+    /// Symlinks are replaced only when they still point where the last run
+    /// left them, like files are only overwritten while pristine.
+    async fn link<T: Target>(
+        &self,
+        target: &T,
+        file: &File,
+        link: &str,
+    ) -> Result<WriteOutcome, GeenieError> {
+        let recorded = self.metadata.links.get(file.path.as_str());
+
+        if !target.exists(&file.path).await? {
+            if recorded.is_some() {
+                return Ok(WriteOutcome::Skipped);
+            }
+            target.symlink(&file.path, link).await?;
+            return Ok(WriteOutcome::Created);
+        }
+
+        match target.read_link(&file.path).await? {
+            Some(current) if current == link => Ok(WriteOutcome::Unchanged),
+            Some(current) if recorded == Some(&current) => {
+                target.symlink(&file.path, link).await?;
+                Ok(WriteOutcome::Overwritten)
+            }
+            _ => Ok(WriteOutcome::Skipped),
+        }
+    }
+
+    async fn resolve<T: Target>(
+        &self,
+        target: &T,
+        file: &File,
+    ) -> Result<(Option<(RelativePathBuf, Vec<u8>)>, WriteOutcome), GeenieError> {
+        let Some(current) = target.read(&file.path).await? else {
+            if self.metadata.files.contains_key(file.path.as_str()) {
+                return Ok((None, WriteOutcome::Skipped));
+            }
+            return Ok((
+                Some((file.path.clone(), file.content.clone())),
+                WriteOutcome::Created,
+            ));
+        };
+
+        if current == file.content {
+            return Ok((None, WriteOutcome::Unchanged));
+        }
+
+        if self.metadata.is_pristine(&file.path, &current) {
+            return Ok((
+                Some((file.path.clone(), file.content.clone())),
+                WriteOutcome::Overwritten,
+            ));
+        }
+
+        if is_binary(&current) || is_binary(&file.content) {
+            let sidecar = RelativePathBuf::from(format!("{}.new", file.path));
+            return Ok((
+                Some((sidecar.clone(), file.content.clone())),
+                WriteOutcome::Sidecar(sidecar),
+            ));
+        }
+
+        let current = String::from_utf8_lossy(&current);
+        let generated = String::from_utf8_lossy(&file.content);
+
+        let base = match &self.base {
+            Some(base) => base
+                .into_iter()
+                .find(|candidate| candidate.path == file.path)
+                .filter(|candidate| !is_binary(&candidate.content))
+                .map(|candidate| String::from_utf8_lossy(&candidate.content)),
+            None => self
+                .metadata
+                .base
+                .get(file.path.as_str())
+                .map(|base| Cow::Borrowed(base.as_str())),
+        };
+
+        let (merged, conflicts) = match base {
+            Some(base) => match diffy::merge(&base, &current, &generated) {
+                Ok(merged) => (merged, false),
+                Err(merged) => (merged, true),
+            },
+            None => crate::diff::merge(&current, &generated),
+        };
+
+        Ok((
+            Some((file.path.clone(), merged.into_bytes())),
+            WriteOutcome::Merged { conflicts },
+        ))
+    }
+}
+
+/// Give an existing path the mode the generator asks for.
+async fn sync_mode<T: Target>(target: &T, file: &File) -> Result<(), GeenieError> {
+    let Some(mode) = file.mode else {
+        return Ok(());
+    };
+
+    if target.read_mode(&file.path).await? != Some(mode) {
+        target.set_mode(&file.path, mode).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::MemoryTarget;
+
+    fn project(name: &str) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("geenie-update-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    fn files(files: &[(&str, &str)]) -> FileList {
+        files
+            .iter()
+            .map(|(path, content)| File::new(*path, *content))
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    fn generate(root: &Path, generated: &FileList) -> Metadata {
+        let mut metadata = Metadata::new("demo", "1", Session::default());
+        metadata.track(generated);
+        for file in generated {
+            std::fs::write(file.path.to_logical_path(root), &file.content).unwrap();
+        }
+        metadata
+    }
+
+    fn outcome(report: &WriteReport, path: &str) -> WriteOutcome {
+        report
+            .files
+            .iter()
+            .find(|file| file.path == path)
+            .map(|file| file.outcome.clone())
+            .unwrap()
+    }
+
+    #[test]
+    fn records_generated_hash_after_merge() {
+        let root = project("hash");
+        let metadata = generate(&root, &files(&[("a.txt", "one\ntwo\nthree\n")]));
+        std::fs::write(root.join("a.txt"), "one\ntwo\nthree\nmine\n").unwrap();
+
+        let next = files(&[("a.txt", "zero\none\ntwo\nthree\n")]);
+        let report = block_on(Update::new(metadata).apply(&root, "2", &next)).unwrap();
+
+        assert_eq!(
+            outcome(&report, "a.txt"),
+            WriteOutcome::Merged { conflicts: false }
+        );
+        assert_eq!(
+            std::fs::read_to_string(root.join("a.txt")).unwrap(),
+            "zero\none\ntwo\nthree\nmine\n"
+        );
+
+        let metadata = block_on(Metadata::load(&root)).unwrap();
+        assert_eq!(metadata.version, "2");
+        assert_eq!(metadata.files["a.txt"], hash(b"zero\none\ntwo\nthree\n"));
+        assert_eq!(metadata.base["a.txt"], "zero\none\ntwo\nthree\n");
+        assert!(!metadata.is_pristine(RelativePath::new("a.txt"), b"zero\none\ntwo\nthree\nmine\n"));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn overwrites_pristine_and_keeps_deleted_files_deleted() {
+        let root = project("pristine");
+        let metadata = generate(&root, &files(&[("a.txt", "a"), ("b.txt", "b")]));
+        std::fs::remove_file(root.join("b.txt")).unwrap();
+
+        let next = files(&[("a.txt", "A"), ("b.txt", "B"), ("c.txt", "C")]);
+        let report = block_on(Update::new(metadata).apply(&root, "2", &next)).unwrap();
+
+        assert_eq!(outcome(&report, "a.txt"), WriteOutcome::Overwritten);
+        assert_eq!(outcome(&report, "b.txt"), WriteOutcome::Skipped);
+        assert_eq!(outcome(&report, "c.txt"), WriteOutcome::Created);
+        assert!(!root.join("b.txt").exists());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn removes_dropped_files_unless_modified() {
+        let root = project("dropped");
+        let metadata = generate(
+            &root,
+            &files(&[("keep.txt", "k"), ("gone.txt", "g"), ("edited.txt", "e")]),
+        );
+        std::fs::write(root.join("edited.txt"), "changed").unwrap();
+
+        let report =
+            block_on(Update::new(metadata).apply(&root, "2", &files(&[("keep.txt", "k")])))
+                .unwrap();
+
+        assert_eq!(outcome(&report, "keep.txt"), WriteOutcome::Unchanged);
+        assert_eq!(outcome(&report, "gone.txt"), WriteOutcome::Removed);
+        assert_eq!(outcome(&report, "edited.txt"), WriteOutcome::Skipped);
+        assert!(!root.join("gone.txt").exists());
+        assert!(root.join("edited.txt").exists());
+
+        let metadata = block_on(Metadata::load(&root)).unwrap();
+        assert_eq!(metadata.files.keys().collect::<Vec<_>>(), vec!["keep.txt"]);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn conflicting_edits_are_marked() {
+        let root = project("conflict");
+        let metadata = generate(&root, &files(&[("a.txt", "value = 1\n")]));
+        std::fs::write(root.join("a.txt"), "value = 2\n").unwrap();
+
+        let next = files(&[("a.txt", "value = 3\n")]);
+        let report = block_on(Update::new(metadata).apply(&root, "2", &next)).unwrap();
+
+        assert_eq!(
+            outcome(&report, "a.txt"),
+            WriteOutcome::Merged { conflicts: true }
+        );
+        assert!(std::fs::read_to_string(root.join("a.txt"))
+            .unwrap()
+            .contains("<<<<<<<"));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn reapplies_modes_to_unchanged_files() {
+        let target = MemoryTarget::new().with_file("run.sh", "echo");
+        let mut metadata = Metadata::new("demo", "1", Session::default());
+        metadata.track(&files(&[("run.sh", "echo")]));
+
+        let next = FileList::from(vec![File::new("run.sh", "echo").executable()]);
+        let report = block_on(Update::new(metadata).apply_into(&target, "2", &next)).unwrap();
+
+        assert_eq!(outcome(&report, "run.sh"), WriteOutcome::Unchanged);
+        assert_eq!(target.mode("run.sh"), Some(0o755));
+        assert_eq!(block_on(Metadata::load_from(&target)).unwrap().version, "2");
+    }
+
+    #[test]
+    fn replaces_symlinks_only_while_pristine() {
+        let target = MemoryTarget::new();
+        block_on(async {
+            target.symlink("current".as_ref(), "v1").await?;
+            target.symlink("pinned".as_ref(), "mine").await?;
+            target.symlink("same".as_ref(), "v2").await?;
+            target.symlink("old".as_ref(), "v1").await
+        })
+        .unwrap();
+
+        let mut metadata = Metadata::new("demo", "1", Session::default());
+        metadata.track(&FileList::from(vec![
+            File::symlink("current", "v1"),
+            File::symlink("pinned", "v1"),
+            File::symlink("same", "v1"),
+            File::symlink("old", "v1"),
+        ]));
+
+        let next = FileList::from(vec![
+            File::symlink("current", "v2"),
+            File::symlink("pinned", "v2"),
+            File::symlink("same", "v2"),
+        ]);
+        let report = block_on(Update::new(metadata).apply_into(&target, "2", &next)).unwrap();
+
+        assert_eq!(outcome(&report, "current"), WriteOutcome::Overwritten);
+        assert_eq!(outcome(&report, "pinned"), WriteOutcome::Skipped);
+        assert_eq!(outcome(&report, "same"), WriteOutcome::Unchanged);
+        assert_eq!(outcome(&report, "old"), WriteOutcome::Removed);
+        assert_eq!(target.link("current").as_deref(), Some("v2"));
+        assert_eq!(target.link("pinned").as_deref(), Some("mine"));
+        assert_eq!(target.link("old"), None);
+    }
+}